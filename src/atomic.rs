@@ -0,0 +1,83 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{chown, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Context;
+
+/// Tells apart the temporary files of concurrent writes within this process.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file next to `target`, it has to be on the same file system for the rename to be atomic.
+fn temporary_path(target: &Path) -> PathBuf {
+    let name = target.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+
+    target.with_file_name(format!(".{}.{}-{}.tmp", name, process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Replaces the content of `path` with `contents` without ever leaving a partially written file behind.
+///
+/// The content is written and synced to a temporary file in the same directory, which is then renamed over the
+/// original. The permissions and, where allowed, the owner of the original are kept. When `path` is a symlink, the
+/// file it points to is replaced and the link itself stays untouched.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let target = fs::canonicalize(path).with_context(|| format!("Failed to resolve {}", path.display()))?;
+    let metadata = fs::metadata(&target).with_context(|| format!("Failed to read the metadata of {}", path.display()))?;
+    let temporary = temporary_path(&target);
+
+    let result = (|| {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(&temporary)?;
+
+        file.write_all(contents)?;
+        file.set_permissions(metadata.permissions())?;
+
+        // Only root may hand a file to another user, anyone else keeps owning the rewritten file.
+        let _ = chown(&temporary, Some(metadata.uid()), Some(metadata.gid()));
+
+        file.sync_all()?;
+
+        fs::rename(&temporary, &target)
+    })();
+
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temporary);
+
+        return Err(error).with_context(|| format!("Failed to write {}", path.display()));
+    }
+
+    // Makes the rename itself durable, not every file system supports syncing a directory so failures are ignored.
+    if let Some(directory) = target.parent() {
+        let _ = File::open(directory).and_then(|directory| directory.sync_all());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    use tempfile::tempdir;
+
+    use crate::atomic::write_atomically;
+
+    #[test]
+    fn it_keeps_permissions_and_symlinks() {
+        let directory = tempdir().unwrap();
+        let root = directory.path();
+
+        fs::write(root.join("script.php"), "<?php\n").unwrap();
+        fs::set_permissions(root.join("script.php"), fs::Permissions::from_mode(0o750)).unwrap();
+        symlink("script.php", root.join("link.php")).unwrap();
+
+        write_atomically(&root.join("link.php"), b"<?php\n$a = 1;\n").unwrap();
+
+        assert!(fs::symlink_metadata(root.join("link.php")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(root.join("script.php")).unwrap(), "<?php\n$a = 1;\n");
+        assert_eq!(fs::metadata(root.join("script.php")).unwrap().permissions().mode() & 0o777, 0o750);
+        assert_eq!(fs::read_dir(root).unwrap().count(), 2);
+    }
+}
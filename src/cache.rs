@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+
+pub const CACHE_FILE: &str = ".php-code-formatter.cache";
+
+const VERSION_PREFIX: &str = "version\t";
+const FIXERS_PREFIX: &str = "fixers\t";
+const OPTIONS_PREFIX: &str = "options\t";
+
+/// Remembers the content hash of every file that is known to be formatted already, so it can be skipped without
+/// being parsed again.
+///
+/// The cache is only valid for the exact binary, fixer set and runner options that produced it, they are all stored
+/// in the file header and the whole cache is discarded when any of them differs.
+pub struct Cache {
+    version: String,
+    fixers: String,
+    options: String,
+    entries: HashMap<PathBuf, u64>,
+}
+
+impl Cache {
+    pub fn new(fixers: &[&str], options: &str) -> Self {
+        Self {
+            version: binary_version(),
+            fixers: fixers.join(","),
+            options: options.to_owned(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Loads the cache stored at `path`, an empty cache is returned when it is missing, corrupted or stale.
+    pub fn load(path: &Path, fixers: &[&str], options: &str) -> Self {
+        let mut cache = Self::new(fixers, options);
+
+        let Ok(content) = fs::read_to_string(path) else {
+            return cache;
+        };
+
+        let mut lines = content.lines();
+
+        let version = lines.next().and_then(|line| line.strip_prefix(VERSION_PREFIX));
+        let fixers = lines.next().and_then(|line| line.strip_prefix(FIXERS_PREFIX));
+        let options = lines.next().and_then(|line| line.strip_prefix(OPTIONS_PREFIX));
+
+        if version != Some(cache.version.as_str()) || fixers != Some(cache.fixers.as_str()) || options != Some(cache.options.as_str()) {
+            return cache;
+        }
+
+        for line in lines {
+            if let Some((hash, path)) = line.split_once('\t') {
+                if let Ok(hash) = u64::from_str_radix(hash, 16) {
+                    cache.entries.insert(PathBuf::from(path), hash);
+                }
+            }
+        }
+
+        cache
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+
+        entries.sort();
+
+        let mut content = format!(
+            "{}{}\n{}{}\n{}{}\n",
+            VERSION_PREFIX, self.version, FIXERS_PREFIX, self.fixers, OPTIONS_PREFIX, self.options,
+        );
+
+        for (file, hash) in entries {
+            content.push_str(&format!("{:016x}\t{}\n", hash, file.display()));
+        }
+
+        fs::write(path, content).with_context(|| format!("Failed to write cache {}", path.display()))
+    }
+
+    pub fn is_formatted(&self, path: &Path, hash: u64) -> bool {
+        self.entries.get(path) == Some(&hash)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, hash: u64) {
+        self.entries.insert(path, hash);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.entries.remove(path);
+    }
+}
+
+/// 64 bit FNV-1a, stable across Rust releases unlike the `std` hasher.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// The crate version together with the fingerprint of the running executable.
+pub fn binary_version() -> String {
+    format!("{}+{}", env!("CARGO_PKG_VERSION"), binary_fingerprint())
+}
+
+/// Identifies the running executable, so that a rebuilt binary never trusts results of the previous one.
+fn binary_fingerprint() -> String {
+    env::current_exe()
+        .and_then(fs::metadata)
+        .map(|metadata| {
+            let modified = metadata.modified().ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default();
+
+            format!("{:x}-{:x}", metadata.len(), modified)
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use tempfile::tempdir;
+
+    use crate::cache::{Cache, content_hash};
+
+    #[test]
+    fn it_remembers_formatted_files_between_runs() {
+        let directory = tempdir().unwrap();
+        let file = directory.path().join("cache");
+        let hash = content_hash(b"<?php\n");
+
+        let mut cache = Cache::new(&["normalizer"], "");
+        cache.insert(PathBuf::from("src/Example.php"), hash);
+        cache.save(&file).unwrap();
+
+        let cache = Cache::load(&file, &["normalizer"], "");
+
+        assert!(cache.is_formatted(Path::new("src/Example.php"), hash));
+        assert!(!cache.is_formatted(Path::new("src/Example.php"), content_hash(b"<?php $a=1;\n")));
+    }
+
+    #[test]
+    fn it_is_invalidated_when_the_fixer_set_or_the_options_change() {
+        let directory = tempdir().unwrap();
+        let file = directory.path().join("cache");
+        let hash = content_hash(b"<?php\n");
+
+        let mut cache = Cache::new(&["normalizer"], "");
+        cache.insert(PathBuf::from("src/Example.php"), hash);
+        cache.save(&file).unwrap();
+
+        let cache = Cache::load(&file, &["normalizer", "header_line"], "");
+
+        assert!(!cache.is_formatted(Path::new("src/Example.php"), hash));
+
+        let cache = Cache::load(&file, &["normalizer"], "max-iterations=1");
+
+        assert!(!cache.is_formatted(Path::new("src/Example.php"), hash));
+    }
+}
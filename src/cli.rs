@@ -0,0 +1,185 @@
+use std::ops::{Range, RangeInclusive};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::cache::CACHE_FILE;
+use crate::daemon::default_socket;
+use crate::fixer::DEFAULT_MAX_ITERATIONS;
+use crate::range::line_range_to_bytes;
+use crate::reporters::ReportFormat;
+
+/// Every file is already formatted, or has been formatted successfully.
+pub const EXIT_SUCCESS: u8 = 0;
+
+/// In check mode, at least one file would be changed by the formatter.
+pub const EXIT_CHANGES_NEEDED: u8 = 1;
+
+/// At least one file could not be read, parsed or formatted.
+pub const EXIT_ERROR: u8 = 2;
+
+/// An opinionated PHP code formatter.
+#[derive(Parser, Debug)]
+#[command(name = "php-code-formatter", version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Files or directories to format, directories are searched recursively for `*.php` and `*.phtml` files.
+    #[arg(default_value = ".")]
+    pub paths: Vec<PathBuf>,
+
+    /// Do not write anything, only list the files that would be changed.
+    ///
+    /// Exits with 0 when every file is formatted, 1 when changes are needed and 2 on errors.
+    #[arg(long)]
+    pub check: bool,
+
+    /// Do not write anything, print a unified diff of the changes instead.
+    ///
+    /// Implies `--check`, the output can be applied with `git apply` or `patch -p1`.
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How the results of `--check` are reported.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "text", requires = "check", conflicts_with = "diff")]
+    pub format: ReportFormat,
+
+    /// Skip paths matching this gitignore style pattern, on top of `.gitignore` and `.phpformatignore` files.
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Apply the ignore rules to files named explicitly on the command line or through `--stdin-filepath` too.
+    #[arg(long)]
+    pub force_exclude: bool,
+
+    /// Only format PHP files that were modified or added since the given git revision, as well as untracked ones.
+    #[arg(long, value_name = "REV")]
+    pub changed_since: Option<String>,
+
+    /// Together with `--changed-since`, only format the lines that changed inside each file.
+    #[arg(long, requires = "changed_since", conflicts_with_all = ["lines", "byte_range"])]
+    pub changed_lines_only: bool,
+
+    /// Only format the smallest syntax nodes covering these 1-based, inclusive lines, e.g. `10-20`.
+    #[arg(long, value_name = "START-END", value_parser = parse_lines, conflicts_with = "byte_range")]
+    pub lines: Option<RangeInclusive<usize>>,
+
+    /// Only format the smallest syntax nodes covering this 0-based, end exclusive byte range, e.g. `120-480`.
+    #[arg(long, value_name = "START-END", value_parser = parse_byte_range)]
+    pub byte_range: Option<Range<usize>>,
+
+    /// Keep running and reformat every PHP file saved inside this directory.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["check", "diff", "stdin", "changed_since", "lines", "byte_range"])]
+    pub watch: Option<PathBuf>,
+
+    /// Format every file a second time and fail, with a diff, for any file the second pass would change again.
+    #[arg(long, conflicts_with_all = ["lines", "byte_range"])]
+    pub verify_idempotent: bool,
+
+    /// Instead of skipping files with syntax errors, format their top-level statements that parsed cleanly.
+    #[arg(long, global = true)]
+    pub allow_syntax_errors: bool,
+
+    /// How many times all fixers may run over a file, each run picking up what the previous one left to do, before
+    /// giving up on it.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_ITERATIONS, global = true)]
+    pub max_iterations: usize,
+
+    /// Print the enabled fixers in the order they run, with what each of them does, and exit.
+    #[arg(long)]
+    pub list_fixers: bool,
+
+    /// The number of files to format concurrently, defaults to the number of CPUs.
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Do not read or write the cache, every file is formatted again.
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Where the cache of already formatted files is stored.
+    #[arg(long, value_name = "PATH", default_value = CACHE_FILE)]
+    pub cache_file: PathBuf,
+
+    /// Read the source code from stdin and write the formatted result to stdout, same as passing `-` as path.
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// The path of the file being formatted from stdin, used for ignore rules and in messages.
+    #[arg(long, value_name = "PATH")]
+    pub stdin_filepath: Option<PathBuf>,
+
+    /// Format through the daemon whenever one is running, instead of in this process.
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// The Unix socket of the daemon, its directory has to be accessible by the current user only.
+    #[arg(long, value_name = "PATH", default_value_os_t = default_socket())]
+    pub socket: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a Language Server Protocol server over stdio, providing document, range and on-type formatting.
+    Lsp,
+
+    /// Keep the parser, compiled queries and cache warm and format the requests sent over a Unix socket.
+    Daemon {
+        /// The Unix socket to listen on, its directory is created with mode 0700 when missing.
+        #[arg(long, value_name = "PATH", default_value_os_t = default_socket())]
+        socket: PathBuf,
+    },
+}
+
+impl Cli {
+    /// Whether files should be left untouched on disk.
+    pub fn is_dry_run(&self) -> bool {
+        self.check || self.diff
+    }
+
+    /// Whether only part of the file should be formatted.
+    pub fn is_range(&self) -> bool {
+        self.lines.is_some() || self.byte_range.is_some()
+    }
+
+    /// The byte range to format within `source_code`, if any.
+    pub fn byte_range(&self, source_code: &[u8]) -> Option<Range<usize>> {
+        match (&self.lines, &self.byte_range) {
+            (Some(lines), _) => Some(line_range_to_bytes(source_code, lines)),
+            (None, Some(range)) => Some(range.start.min(source_code.len())..range.end.min(source_code.len())),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether the source code should be read from stdin instead of the given paths.
+    pub fn is_stdin(&self) -> bool {
+        self.stdin || self.paths.iter().any(|path| path.as_os_str() == "-")
+    }
+}
+
+fn parse_bounds(value: &str) -> Result<(usize, usize), String> {
+    let (start, end) = value.split_once('-').ok_or(format!("expected START-END, got `{}`", value))?;
+    let start = start.trim().parse::<usize>().map_err(|error| error.to_string())?;
+    let end = end.trim().parse::<usize>().map_err(|error| error.to_string())?;
+
+    if start > end {
+        return Err(format!("the start of `{}` is after its end", value));
+    }
+
+    Ok((start, end))
+}
+
+fn parse_lines(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = parse_bounds(value)?;
+
+    if start == 0 {
+        return Err("lines are numbered from 1".to_owned());
+    }
+
+    Ok(start..=end)
+}
+
+fn parse_byte_range(value: &str) -> Result<Range<usize>, String> {
+    parse_bounds(value).map(|(start, end)| start..end)
+}
@@ -0,0 +1,313 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{binary_version, Cache, content_hash};
+use crate::fixer::FixerRunner;
+use crate::formatter::{FormattedFile, FormattingSession};
+
+/// Where the daemon listens when no `--socket` is given, in a directory of its own that only the current user can
+/// access, so no other user can listen there in its place.
+pub fn default_socket() -> PathBuf {
+    let directory = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) => PathBuf::from(runtime).join("php-code-formatter"),
+        None => env::temp_dir().join(format!("php-code-formatter-{}", current_uid())),
+    };
+
+    directory.join("daemon.sock")
+}
+
+fn current_uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+/// The user of the process at the other end of `stream`.
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+
+    match result {
+        0 => Ok(credentials.uid),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// The user of the process at the other end of `stream`.
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let (mut uid, mut gid) = (0, 0);
+
+    match unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } {
+        0 => Ok(uid),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Fails unless the directory of `socket` belongs to the current user and nobody else can access it, which is what
+/// keeps other users from listening on the socket or connecting to it.
+fn ensure_private(socket: &Path) -> anyhow::Result<()> {
+    let directory = socket.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let metadata = fs::metadata(directory).with_context(|| format!("Failed to read {}", directory.display()))?;
+
+    if metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        bail!("The directory of the daemon socket {} has to belong to the current user with mode 0700", socket.display());
+    }
+
+    Ok(())
+}
+
+/// A single format request, sent by the client as one line of JSON.
+#[derive(Serialize, Deserialize, Debug)]
+struct Request {
+    /// The version of the client, the daemon refuses requests from any other build.
+    version: String,
+    /// The working directory of the client, relative paths are resolved against it.
+    directory: PathBuf,
+    path: PathBuf,
+    contents: String,
+    range: Option<Range<usize>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Response {
+    Formatted { formatted: String, applied_fixers: Vec<String> },
+    Failed { error: String },
+    Incompatible { version: String },
+}
+
+struct Daemon {
+    cache: Mutex<Cache>,
+}
+
+impl Daemon {
+    fn format(&self, session: &mut FormattingSession, request: Request) -> Response {
+        if request.version != binary_version() {
+            return Response::Incompatible { version: binary_version() };
+        }
+
+        let key = request.directory.join(&request.path);
+        let hash = content_hash(request.contents.as_bytes());
+
+        if request.range.is_none() && self.cache.lock().unwrap().is_formatted(&key, hash) {
+            return Response::Formatted { formatted: request.contents, applied_fixers: vec![] };
+        }
+
+        let result = session.format(&request.path, request.contents.into_bytes(), request.range.clone());
+
+        let file = match result {
+            Ok(file) => file,
+            Err(error) => return Response::Failed { error: format!("{:#}", error) },
+        };
+
+        // Formatting never breaks valid UTF-8 apart, this is only a safeguard against sending back a lossy buffer.
+        let Ok(formatted) = String::from_utf8(file.formatted) else {
+            return Response::Failed { error: format!("The formatted {} is not valid UTF-8", file.path.display()) };
+        };
+
+        // The client writes the result to disk, so the next request for the same file is a cache hit.
+        if request.range.is_none() {
+            self.cache.lock().unwrap().insert(key, content_hash(formatted.as_bytes()));
+        }
+
+        Response::Formatted { formatted, applied_fixers: file.applied_fixers }
+    }
+
+    fn handle(&self, session: &mut FormattingSession, stream: UnixStream) -> anyhow::Result<()> {
+        // The daemon reads files and writes the result back to whoever asks, only ever to the user running it.
+        if peer_uid(&stream).context("Failed to identify the client")? != current_uid() {
+            bail!("Refused a connection from another user");
+        }
+
+        let mut line = String::new();
+
+        BufReader::new(&stream).read_line(&mut line).context("Failed to read the request")?;
+
+        // Connecting without sending anything is how a second daemon checks whether this one is alive.
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => self.format(session, request),
+            Err(error) => Response::Failed { error: format!("Invalid request: {}", error) },
+        };
+
+        serde_json::to_writer(&stream, &response).context("Failed to write the response")?;
+
+        (&stream).write_all(b"\n").context("Failed to write the response")
+    }
+}
+
+/// Answers format requests from `listener` until the process is killed.
+///
+/// Connections are handed to a fixed set of threads, each keeping its own formatting session for as long as the daemon runs.
+pub fn serve<F: Fn() -> FixerRunner + Sync>(listener: UnixListener, jobs: usize, create_runner: F) -> anyhow::Result<()> {
+    let runner = create_runner();
+    let daemon = Daemon { cache: Mutex::new(Cache::new(&runner.fixer_names(), &runner.options())) };
+    let (sender, receiver) = channel::<UnixStream>();
+    let receiver = Mutex::new(receiver);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            let (daemon, receiver, create_runner) = (&daemon, &receiver, &create_runner);
+
+            scope.spawn(move || -> anyhow::Result<()> {
+                let mut session = FormattingSession::new(create_runner())?;
+
+                loop {
+                    // The lock is only held while waiting, it is released as soon as a connection was received.
+                    let Ok(stream) = receiver.lock().unwrap().recv() else {
+                        return Ok(());
+                    };
+
+                    if let Err(error) = daemon.handle(&mut session, stream) {
+                        eprintln!("Warning: {:#}", error);
+                    }
+                }
+            });
+        }
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => sender.send(stream)?,
+                Err(error) => eprintln!("Warning: {}", error),
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Binds `socket` and serves format requests on it, a stale socket left behind by a killed daemon is replaced.
+///
+/// The directory of the socket is created when missing, it has to be private to the current user.
+pub fn run<F: Fn() -> FixerRunner + Sync>(socket: &Path, jobs: usize, create_runner: F) -> anyhow::Result<()> {
+    if let Some(directory) = socket.parent().filter(|parent| !parent.as_os_str().is_empty() && !parent.exists()) {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(directory)
+            .with_context(|| format!("Failed to create {}", directory.display()))?;
+    }
+
+    ensure_private(socket)?;
+
+    if UnixStream::connect(socket).is_ok() {
+        bail!("A daemon is already listening on {}", socket.display());
+    }
+
+    let _ = fs::remove_file(socket);
+
+    let listener = UnixListener::bind(socket).with_context(|| format!("Failed to listen on {}", socket.display()))?;
+
+    println!("Listening on {}", socket.display());
+
+    serve(listener, jobs, create_runner)
+}
+
+/// Formats `original` through the daemon listening on `socket`.
+///
+/// `None` is returned whenever the daemon can't be used, because none is running, it was built from another version
+/// or the source isn't UTF-8, so the caller can format the file itself. Errors about the file itself are returned as
+/// such, and so is a socket that another user could have put there, its answer would be written to the file as is.
+pub fn forward(socket: &Path, path: &Path, original: &[u8], range: Option<Range<usize>>) -> anyhow::Result<Option<FormattedFile>> {
+    let Ok(contents) = String::from_utf8(original.to_vec()) else {
+        return Ok(None);
+    };
+
+    if !socket.exists() {
+        return Ok(None);
+    }
+
+    ensure_private(socket)?;
+
+    let Ok(stream) = UnixStream::connect(socket) else {
+        return Ok(None);
+    };
+
+    if peer_uid(&stream).context("Failed to identify the daemon")? != current_uid() {
+        bail!("The daemon listening on {} belongs to another user", socket.display());
+    }
+
+    let directory = env::current_dir().context("Failed to read the current directory")?;
+    let request = Request { version: binary_version(), directory, path: path.to_owned(), contents, range };
+    let mut line = serde_json::to_string(&request)?;
+
+    line.push('\n');
+
+    let mut response = String::new();
+
+    let sent = (&stream).write_all(line.as_bytes())
+        .and_then(|_| BufReader::new(&stream).read_line(&mut response));
+
+    match (sent, serde_json::from_str::<Response>(&response)) {
+        (Ok(_), Ok(Response::Formatted { formatted, applied_fixers })) => Ok(Some(FormattedFile {
+            path: path.to_owned(),
+            original: original.to_vec(),
+            formatted: formatted.into_bytes(),
+            applied_fixers,
+            // The daemon refuses code with syntax errors.
+            has_syntax_errors: false,
+        })),
+        (Ok(_), Ok(Response::Failed { error })) => bail!(error),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::thread;
+
+    use tempfile::tempdir;
+
+    use crate::daemon::{forward, serve};
+    use crate::fixer::default_runner;
+
+    #[test]
+    fn it_formats_through_the_daemon() {
+        let directory = tempdir().unwrap();
+        let socket = directory.path().join("daemon.sock");
+
+        fs::set_permissions(directory.path(), fs::Permissions::from_mode(0o700)).unwrap();
+
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        thread::spawn(move || serve(listener, 2, default_runner));
+
+        let path = Path::new("test.php");
+        let file = forward(&socket, path, b"<?php\n$a  =  1;\n", None).unwrap().unwrap();
+
+        assert_eq!(String::from_utf8(file.formatted).unwrap(), "<?php\n$a = 1;\n");
+        assert!(forward(&socket, path, b"<?php\n$a = ;\n", None).is_err());
+        assert!(forward(&socket.with_extension("missing"), path, b"<?php\n", None).unwrap().is_none());
+
+        // A socket in a directory other users can write to may not be theirs.
+        fs::set_permissions(directory.path(), fs::Permissions::from_mode(0o777)).unwrap();
+
+        assert!(forward(&socket, path, b"<?php\n$a  =  1;\n", None).is_err());
+    }
+}
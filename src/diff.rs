@@ -0,0 +1,130 @@
+use std::env;
+use std::path::{Component, Path};
+
+use similar::TextDiff;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Returns the path as it should appear in a patch header, relative to the working directory and `/` separated.
+/// An absolute path outside of the working directory has no such form, `None` is returned for it.
+pub fn patch_path(path: &Path) -> Option<String> {
+    let relative = match path.is_absolute() {
+        true => path.strip_prefix(env::current_dir().ok()?).ok()?,
+        false => path,
+    };
+
+    let path = relative.components()
+        .filter(|component| matches!(component, Component::Normal(_) | Component::ParentDir))
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    Some(path)
+}
+
+/// Builds a unified diff between the original and the formatted source, with `a/` and `b/` prefixed file headers
+/// so the output can be fed to `git apply` or `patch -p1`. Files outside of the working directory keep their
+/// absolute path in the headers instead, without a prefix.
+pub fn unified_diff(path: &Path, original: &[u8], formatted: &[u8], colored: bool) -> String {
+    let original = String::from_utf8_lossy(original);
+    let formatted = String::from_utf8_lossy(formatted);
+
+    let (old, new) = match patch_path(path) {
+        Some(path) => (format!("a/{}", path), format!("b/{}", path)),
+        None => (path.to_string_lossy().into_owned(), path.to_string_lossy().into_owned()),
+    };
+
+    let diff = TextDiff::from_lines(original.as_ref(), formatted.as_ref())
+        .unified_diff()
+        .context_radius(3)
+        .header(&old, &new)
+        .to_string();
+
+    match colored {
+        true => colorize(&diff),
+        false => diff,
+    }
+}
+
+fn colorize(diff: &str) -> String {
+    diff.split_inclusive('\n')
+        .map(|line| {
+            let color = if line.starts_with("---") || line.starts_with("+++") {
+                BOLD
+            } else if line.starts_with("@@") {
+                CYAN
+            } else if line.starts_with('-') {
+                RED
+            } else if line.starts_with('+') {
+                GREEN
+            } else {
+                return line.to_owned();
+            };
+
+            let content = line.trim_end_matches('\n');
+            let line_break = &line[content.len()..];
+
+            format!("{}{}{}{}", color, content, RESET, line_break)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use crate::diff::{patch_path, unified_diff};
+
+    #[test]
+    fn it_produces_a_patch_with_git_style_headers() {
+        let original = indoc! {"
+        <?php
+        $a=1;
+        "};
+
+        let formatted = indoc! {"
+        <?php
+        $a = 1;
+        "};
+
+        let expected = indoc! {"
+        --- a/src/Example.php
+        +++ b/src/Example.php
+        @@ -1,2 +1,2 @@
+         <?php
+        -$a=1;
+        +$a = 1;
+        "};
+
+        let diff = unified_diff(Path::new("./src/Example.php"), original.as_bytes(), formatted.as_bytes(), false);
+
+        assert_eq!(diff, expected);
+    }
+
+    #[test]
+    fn it_keeps_absolute_paths_outside_of_the_working_directory() {
+        let outside = std::env::current_dir().unwrap().parent().unwrap().join("elsewhere/Example.php");
+
+        assert_eq!(patch_path(&outside), None);
+        assert_eq!(patch_path(&std::env::current_dir().unwrap().join("src/Example.php")).as_deref(), Some("src/Example.php"));
+
+        let diff = unified_diff(&outside, b"<?php\n$a=1;\n", b"<?php\n$a = 1;\n", false);
+
+        assert!(diff.starts_with(&format!("--- {}\n+++ {}\n", outside.display(), outside.display())));
+    }
+
+    #[test]
+    fn it_only_colors_changed_lines() {
+        let diff = unified_diff(Path::new("Example.php"), b"<?php\n$a=1;\n", b"<?php\n$a = 1;\n", true);
+
+        assert!(diff.contains("\x1b[31m-$a=1;\x1b[0m\n"));
+        assert!(diff.contains("\x1b[32m+$a = 1;\x1b[0m\n"));
+        assert!(diff.contains("\n <?php\n"));
+    }
+}
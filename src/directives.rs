@@ -0,0 +1,140 @@
+use std::ops::Range;
+
+use tree_sitter::{Node, Tree};
+
+use crate::range::merge;
+
+/// Every directive starts with this, source code without it is never looked at any further.
+const PREFIX: &[u8] = b"@formatter:";
+
+enum Directive {
+    /// `// @formatter:off`, nothing is formatted until the next `// @formatter:on` or the end of the file.
+    Off,
+    On,
+    /// `// @formatter:ignore-next-line`
+    IgnoreNextLine,
+    /// A `@formatter:ignore` tag in a docblock, protecting the whole class or function that follows it.
+    IgnoreDeclaration,
+}
+
+fn directive(comment: &[u8]) -> Option<Directive> {
+    let text = String::from_utf8_lossy(comment);
+
+    if text.starts_with("/**") {
+        return text.split_whitespace().any(|word| word == "@formatter:ignore").then_some(Directive::IgnoreDeclaration);
+    }
+
+    match text.trim_start_matches(['/', '#', '*']).trim_end_matches(['*', '/']).trim() {
+        "@formatter:off" => Some(Directive::Off),
+        "@formatter:on" => Some(Directive::On),
+        "@formatter:ignore-next-line" => Some(Directive::IgnoreNextLine),
+        _ => None,
+    }
+}
+
+fn comments<'a>(node: Node<'a>, comments: &mut Vec<Node<'a>>) {
+    match node.kind() {
+        "comment" => comments.push(node),
+        _ => node.children(&mut node.walk()).for_each(|child| self::comments(child, comments)),
+    }
+}
+
+/// The byte ranges of `source_code` that directives protect from being formatted, sorted and without overlaps.
+pub fn protected_regions(tree: &Tree, source_code: &[u8]) -> Vec<Range<usize>> {
+    if !source_code.windows(PREFIX.len()).any(|window| window == PREFIX) {
+        return vec![];
+    }
+
+    let mut nodes = vec![];
+    let mut regions: Vec<Range<usize>> = vec![];
+    let mut off: Option<usize> = None;
+
+    comments(tree.root_node(), &mut nodes);
+
+    for comment in nodes {
+        match directive(&source_code[comment.byte_range()]) {
+            Some(Directive::Off) => off = off.or(Some(comment.start_byte())),
+            Some(Directive::On) => regions.extend(off.take().map(|start| start..comment.end_byte())),
+            Some(Directive::IgnoreNextLine) => {
+                let next_line = source_code[comment.end_byte()..].iter().position(|byte| *byte == b'\n')
+                    .map_or(source_code.len(), |offset| comment.end_byte() + offset + 1);
+                let end = source_code[next_line..].iter().position(|byte| *byte == b'\n')
+                    .map_or(source_code.len(), |offset| next_line + offset);
+
+                regions.push(comment.start_byte()..end);
+            }
+            Some(Directive::IgnoreDeclaration) => {
+                regions.extend(comment.next_named_sibling().map(|declaration| comment.start_byte()..declaration.end_byte()));
+            }
+            None => {}
+        }
+    }
+
+    regions.extend(off.map(|start| start..source_code.len()));
+
+    merge(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::fixer::default_runner;
+
+    fn format(source_code: &str) -> String {
+        let mut runner = default_runner();
+        let mut source_code = source_code.as_bytes().to_vec();
+
+        runner.execute(&mut source_code).unwrap();
+
+        String::from_utf8(source_code).unwrap()
+    }
+
+    #[test]
+    fn it_keeps_code_between_off_and_on_as_is() {
+        assert_eq!(format(indoc! {"
+            <?php
+            $a  =  1 ;
+            // @formatter:off
+            $table = [
+                1,   2,   3,
+                40,  50,  60,
+            ];
+            // @formatter:on
+            $b  =  2 ;
+        "}), indoc! {"
+            <?php
+            $a = 1;
+            // @formatter:off
+            $table = [
+                1,   2,   3,
+                40,  50,  60,
+            ];
+            // @formatter:on
+            $b = 2;
+        "});
+    }
+
+    #[test]
+    fn it_keeps_the_next_line_and_ignored_declarations_as_is() {
+        assert_eq!(format(indoc! {"
+            <?php
+            // @formatter:ignore-next-line
+            $a  =  1 ;
+            $b  =  2 ;
+            /**
+             * @formatter:ignore
+             */
+            function  a( $x ) { return   $x; }
+        "}), indoc! {"
+            <?php
+            // @formatter:ignore-next-line
+            $a  =  1 ;
+            $b = 2;
+            /**
+             * @formatter:ignore
+             */
+            function  a( $x ) { return   $x; }
+        "});
+    }
+}
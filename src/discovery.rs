@@ -0,0 +1,181 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+
+pub const PHP_EXTENSIONS: &[&str] = &["php", "phtml"];
+
+/// A file with gitignore syntax listing paths the formatter should never touch, e.g. `vendor/` or generated stubs.
+pub const IGNORE_FILE: &str = ".phpformatignore";
+
+const GIT_IGNORE_FILE: &str = ".gitignore";
+
+pub fn is_php_file(path: &Path) -> bool {
+    path.extension().filter(|extension| PHP_EXTENSIONS.iter().any(|php| extension == php)).is_some()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    match path.is_absolute() {
+        true => path.to_owned(),
+        false => env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_owned()),
+    }
+}
+
+/// Finds the PHP files to format, honouring `.gitignore`, `.phpformatignore` and `--exclude` patterns.
+pub struct Discovery {
+    excludes: Gitignore,
+    force_exclude: bool,
+}
+
+impl Discovery {
+    /// `excludes` are gitignore style patterns relative to the working directory. Files given explicitly are only
+    /// checked against the ignore rules when `force_exclude` is set.
+    pub fn new(excludes: &[String], force_exclude: bool) -> anyhow::Result<Self> {
+        let mut builder = GitignoreBuilder::new(env::current_dir()?);
+
+        for pattern in excludes {
+            builder.add_line(None, pattern).with_context(|| format!("Invalid exclude pattern: {}", pattern))?;
+        }
+
+        Ok(Self { excludes: builder.build()?, force_exclude })
+    }
+
+    /// Expands the given mix of files and directories into a sorted, de-duplicated list of PHP files.
+    ///
+    /// Directories are walked recursively and only `*.php` and `*.phtml` files that are not ignored are collected.
+    pub fn discover(&self, paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+
+        for path in paths {
+            if path.is_file() {
+                if !self.force_exclude || !self.is_excluded(path) {
+                    files.push(path.to_owned());
+                }
+
+                continue;
+            }
+
+            if !path.is_dir() {
+                bail!("No such file or directory: {}", path.display());
+            }
+
+            let excludes = self.excludes.clone();
+
+            let walker = WalkBuilder::new(path)
+                .standard_filters(false)
+                .git_ignore(true)
+                .require_git(false)
+                .parents(true)
+                .add_custom_ignore_filename(IGNORE_FILE)
+                .sort_by_file_name(|left, right| left.cmp(right))
+                .filter_entry(move |entry| {
+                    let is_dir = entry.file_type().is_some_and(|file_type| file_type.is_dir());
+
+                    entry.file_name() != ".git" && !excludes.matched(absolute(entry.path()), is_dir).is_ignore()
+                })
+                .build();
+
+            for entry in walker {
+                let entry = entry.with_context(|| format!("Failed to walk directory {}", path.display()))?;
+
+                if entry.file_type().is_some_and(|file_type| file_type.is_file()) && is_php_file(entry.path()) {
+                    files.push(entry.into_path());
+                }
+            }
+        }
+
+        files.sort();
+        files.dedup();
+
+        Ok(files)
+    }
+
+    /// Whether `path` matches an `--exclude` pattern or an ignore file found in any of its parent directories,
+    /// up to the root of the git repository it belongs to.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let path = absolute(path);
+
+        let excluded = path.ancestors()
+            .enumerate()
+            .any(|(depth, ancestor)| self.excludes.matched(ancestor, depth > 0).is_ignore());
+
+        if excluded {
+            return true;
+        }
+
+        // The closest ignore file with an opinion about the path wins, like git does.
+        for directory in path.ancestors().skip(1) {
+            let mut builder = GitignoreBuilder::new(directory);
+
+            builder.add(directory.join(GIT_IGNORE_FILE));
+            builder.add(directory.join(IGNORE_FILE));
+
+            if let Ok(ignore) = builder.build() {
+                let matched = ignore.matched_path_or_any_parents(&path, false);
+
+                if matched.is_ignore() || matched.is_whitelist() {
+                    return matched.is_ignore();
+                }
+            }
+
+            if directory.join(".git").exists() {
+                break;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use crate::discovery::Discovery;
+
+    #[test]
+    fn it_collects_php_files_recursively() {
+        let directory = tempdir().unwrap();
+        let root = directory.path().to_owned();
+
+        fs::create_dir_all(root.join("app/Models")).unwrap();
+        fs::write(root.join("app/Models/User.php"), "<?php").unwrap();
+        fs::write(root.join("app/helpers.php"), "<?php").unwrap();
+        fs::write(root.join("app/readme.md"), "").unwrap();
+
+        let discovery = Discovery::new(&[], false).unwrap();
+        let files = discovery.discover(&[root.clone(), root.join("app/helpers.php")]).unwrap();
+
+        assert_eq!(files, vec![root.join("app/Models/User.php"), root.join("app/helpers.php")]);
+    }
+
+    #[test]
+    fn it_skips_ignored_paths_unless_named_explicitly() {
+        let directory = tempdir().unwrap();
+        let root = directory.path().to_owned();
+
+        fs::create_dir_all(root.join("vendor/package")).unwrap();
+        fs::create_dir_all(root.join("storage")).unwrap();
+        fs::create_dir_all(root.join("stubs")).unwrap();
+        fs::write(root.join(".gitignore"), "/vendor\n").unwrap();
+        fs::write(root.join(".phpformatignore"), "storage/\n").unwrap();
+        fs::write(root.join("vendor/package/Library.php"), "<?php").unwrap();
+        fs::write(root.join("storage/compiled.php"), "<?php").unwrap();
+        fs::write(root.join("stubs/Model.php"), "<?php").unwrap();
+        fs::write(root.join("index.php"), "<?php").unwrap();
+
+        let discovery = Discovery::new(&["stubs".to_owned()], false).unwrap();
+        let files = discovery.discover(&[root.clone(), root.join("storage/compiled.php")]).unwrap();
+
+        assert_eq!(files, vec![root.join("index.php"), root.join("storage/compiled.php")]);
+
+        let discovery = Discovery::new(&["stubs".to_owned()], true).unwrap();
+        let files = discovery.discover(&[root.clone(), root.join("storage/compiled.php")]).unwrap();
+
+        assert_eq!(files, vec![root.join("index.php")]);
+    }
+}
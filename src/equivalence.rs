@@ -0,0 +1,135 @@
+use anyhow::bail;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::strings::is_string_like;
+
+/// A single token of the source code, the smallest unit a layout only change may move around but never alter.
+#[derive(Debug)]
+struct Token<'a> {
+    kind: &'static str,
+    text: &'a [u8],
+    position: Point,
+}
+
+impl Token<'_> {
+    /// Comments are re-indented by the fixers, so only the words inside them have to stay the same.
+    fn is_equivalent(&self, other: &Token) -> bool {
+        match self.kind {
+            "comment" => self.kind == other.kind && words(self.text).eq(words(other.text)),
+            "heredoc" | "nowdoc" => self.kind == other.kind && heredoc_value(self.text) == heredoc_value(other.text),
+            _ => self.kind == other.kind && self.text == other.text,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("`{}` at line {}, column {}", String::from_utf8_lossy(self.text), self.position.row + 1, self.position.column + 1)
+    }
+}
+
+fn words(text: &[u8]) -> impl Iterator<Item = &[u8]> {
+    text.split(|byte| byte.is_ascii_whitespace()).filter(|word| !word.is_empty())
+}
+
+/// The lines of a heredoc or nowdoc as PHP sees them, without the indentation of the closing marker, which flexible
+/// heredocs are re-indented along with. Lines holding nothing but whitespace are always empty.
+fn heredoc_value(text: &[u8]) -> Vec<&[u8]> {
+    let lines: Vec<&[u8]> = text.split(|byte| *byte == b'\n').collect();
+    let marker = lines.last().map_or(0, |line| line.iter().take_while(|byte| matches!(byte, b' ' | b'\t')).count());
+
+    lines.iter()
+        .enumerate()
+        .map(|(index, line)| match *line {
+            line if index == 0 => line,
+            line if line.iter().all(u8::is_ascii_whitespace) => &line[line.len()..],
+            line => &line[line.iter().take(marker).take_while(|byte| matches!(byte, b' ' | b'\t')).count()..],
+        })
+        .collect()
+}
+
+fn collect<'a>(node: Node, source_code: &'a [u8], tokens: &mut Vec<Token<'a>>) {
+    // Zero width tokens, such as automatic semicolons, are not part of the source code.
+    if node.byte_range().is_empty() {
+        return;
+    }
+
+    // A string is a token as a whole, its content is never a matter of layout.
+    if node.child_count() == 0 || is_string_like(&node) {
+        tokens.push(Token { kind: node.kind(), text: &source_code[node.byte_range()], position: node.start_position() });
+
+        return;
+    }
+
+    node.children(&mut node.walk()).for_each(|child| collect(child, source_code, tokens));
+}
+
+/// Whether the token at `index` is one the fixers are allowed to add or remove, as it does not change what the code
+/// does: a trailing comma before a closing bracket, or the closing PHP tag at the end of a file. The opening tag is
+/// never one of them, `<?php` and `<?=` behave differently.
+fn is_layout(tokens: &[Token], index: usize) -> bool {
+    let next = tokens.get(index + 1).map(|token| token.kind);
+
+    match tokens[index].kind {
+        "," => matches!(next, Some(")" | "]" | "}")),
+        "?>" | "php_end_tag" => next.is_none(),
+        _ => false,
+    }
+}
+
+fn tokens<'a>(tree: &Tree, source_code: &'a [u8]) -> Vec<Token<'a>> {
+    let mut tokens = vec![];
+
+    collect(tree.root_node(), source_code, &mut tokens);
+
+    let layout: Vec<bool> = (0..tokens.len()).map(|index| is_layout(&tokens, index)).collect();
+
+    tokens.into_iter().zip(layout).filter(|(_, layout)| !layout).map(|(token, _)| token).collect()
+}
+
+/// Fails unless `formatted` consists of the same tokens as `original`, apart from whitespace and layout only
+/// tokens, i.e. unless both are guaranteed to behave the same.
+pub fn ensure_equivalent(original: &Tree, original_source: &[u8], formatted: &Tree, formatted_source: &[u8]) -> anyhow::Result<()> {
+    let original = tokens(original, original_source);
+    let formatted = tokens(formatted, formatted_source);
+
+    for index in 0..original.len().max(formatted.len()) {
+        match (original.get(index), formatted.get(index)) {
+            (Some(left), Some(right)) if left.is_equivalent(right) => continue,
+            (Some(left), Some(right)) => bail!("{} became {}", left.describe(), right.describe()),
+            (Some(left), None) => bail!("{} was removed", left.describe()),
+            (None, Some(right)) => bail!("{} was added", right.describe()),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equivalence::ensure_equivalent;
+    use crate::fixer::FixerRunner;
+
+    fn check(original: &str, formatted: &str) -> anyhow::Result<()> {
+        let mut parser = FixerRunner::create_parser().unwrap();
+        let original_tree = FixerRunner::parse(&mut parser, original.as_bytes()).unwrap();
+        let formatted_tree = FixerRunner::parse(&mut parser, formatted.as_bytes()).unwrap();
+
+        ensure_equivalent(&original_tree, original.as_bytes(), &formatted_tree, formatted.as_bytes())
+    }
+
+    #[test]
+    fn it_only_allows_layout_changes() {
+        assert!(check("<?php\nfoo( $a,$b );\n", "<?php\nfoo(\n    $a,\n    $b,\n);\n").is_ok());
+        assert!(check("<?php\n// a  comment\n$a = 'x  y';\n", "<?php\n    //   a comment\n$a = 'x  y';\n").is_ok());
+
+        let error = check("<?php\n$a = 'x  y';\n", "<?php\n$a = 'x y';\n").unwrap_err();
+
+        assert_eq!(error.to_string(), "`'x  y'` at line 2, column 6 became `'x y'` at line 2, column 6");
+        assert!(check("<?php\n$a = - $b;\n", "<?php\n$a = $b;\n").is_err());
+        assert!(check("<?php\nfoo(&$a);\n", "<?php\nfoo($a);\n").is_err());
+        assert!(check("<?php\n$a = 1;\n?>", "<?php\n$a = 1;\n").is_ok());
+        assert!(check("<?php $a;\n", "<?= $a;\n").is_err());
+        assert!(check("<?php\n$a = <<<EOT\n    x\n\n      y\n    EOT;\n", "<?php\n$a = <<<EOT\n  x\n\n    y\n  EOT;\n").is_ok());
+        assert!(check("<?php\n$a = <<<EOT\n    x\n      y\n    EOT;\n", "<?php\n$a = <<<EOT\n  x\n  y\n  EOT;\n").is_err());
+    }
+}
@@ -1,72 +1,492 @@
-use tree_sitter::{Language, Node, Parser, Query, QueryCursor, Tree};
+use std::cmp::Reverse;
+use std::ops::Range;
 
-use crate::test_utilities::{Edit, perform_edit};
+use anyhow::{bail, Context};
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Query, QueryCursor, Tree};
+
+use crate::directives::protected_regions;
+use crate::equivalence::ensure_equivalent;
+use crate::line_index::LineIndex;
+use crate::range::{Change, changes, covering_range, intersects, merge, restore, shift_range, splice};
+use crate::strings::{is_inside_string, moves_heredocs, reindent_heredocs, string_regions};
+use crate::syntax::ensure_valid;
+use crate::test_utilities::Edit;
 
 extern "C" { pub fn tree_sitter_php() -> Language; }
 
 pub trait Fixer {
+    /// A short, stable name identifying the fixer, e.g. in the cache signature, in reports and in the dependencies
+    /// of other fixers. It must never change once released.
+    fn name(&self) -> &'static str;
+
+    /// What the fixer does, in a single sentence.
+    fn description(&self) -> &'static str;
+
+    /// Fixers with a higher priority run first, unless a dependency says otherwise.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// The names of the fixers this one has to run before, when they are enabled.
+    fn runs_before(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The names of the fixers this one has to run after, when they are enabled.
+    fn runs_after(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether the fixer changes the code itself rather than only its layout, e.g. by adding or removing statements.
+    /// The output of such a fixer is not checked for equivalence with its input.
+    fn is_risky(&self) -> bool {
+        false
+    }
+
     fn query(&self) -> &str;
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit>;
+    /// The edit rewriting a node matched by the query, when it needs one.
+    fn fix(&mut self, _node: &Node, _source_code: &[u8]) -> Option<Edit> {
+        None
+    }
+
+    /// Every edit for a node matched by the query, for fixers changing separate parts of a node, e.g. the whitespace
+    /// between its children, so the edits for nested nodes never overlap. Only the edit of `fix` by default.
+    fn fixes(&mut self, node: &Node, source_code: &[u8]) -> Vec<Edit> {
+        self.fix(node, source_code).into_iter().collect()
+    }
 
-    fn execute(&mut self, mut tree: Tree, parser: &mut Parser, source_code: &mut Vec<u8>, language: &Language) -> anyhow::Result<Tree> {
+    /// The edits for every node matched by the query, when `range` is given only for the nodes intersecting it.
+    /// Edits that would not change anything are left out.
+    fn edits(&mut self, tree: &Tree, source_code: &[u8], query: &Query, range: Option<&Range<usize>>) -> Vec<Edit> {
         let mut cursor = QueryCursor::new();
-        let query = Query::new(*language, self.query())?;
-        let mut index = 0;
 
-        loop {
-            let mut nodes: Vec<Node> = cursor
-                .matches(&query, tree.root_node(), source_code.as_slice())
-                .flat_map(|item| item.captures)
-                .map(|capture| capture.node)
-                .collect();
+        let nodes: Vec<Node> = cursor
+            .matches(query, tree.root_node(), source_code)
+            .flat_map(|item| item.captures)
+            .map(|capture| capture.node)
+            .filter(|node| range.is_none_or(|range| intersects(&node.byte_range(), range)))
+            .filter(|node| !is_inside_string(node))
+            .collect();
+
+        nodes.iter()
+            .flat_map(|node| self.fixes(node, source_code))
+            .filter(|edit| source_code.get(edit.position..edit.position + edit.deleted_length) != Some(&edit.inserted_text[..]))
+            .collect()
+    }
+
+    /// Applies every edit of the fixer at once and parses the source code again only once. Edits overlapping each
+    /// other, even when one is nested inside the other, can't all be right and are reported as an error.
+    ///
+    /// `range` is moved along with every edit, so it keeps covering the same code for the next fixer. The parts of the
+    /// source code the fixer changed are returned along with the updated tree.
+    fn execute(&mut self, tree: Tree, parser: &mut Parser, source_code: &mut Vec<u8>, query: &Query, range: Option<&mut Range<usize>>) -> anyhow::Result<(Tree, Vec<Change>)> {
+        let edits = self.edits(&tree, source_code, query, range.as_deref());
 
-            if let Some(node) = nodes.get(index) {
-                index += 1;
+        if edits.is_empty() {
+            return Ok((tree, vec![]));
+        }
 
-                if let Some(edit) = self.fix(&node, source_code) {
-                    if *edit.inserted_text != source_code[node.byte_range()] {
-                        perform_edit(&mut tree, source_code, &edit);
+        let edits = non_overlapping(edits).with_context(|| format!("{} produced conflicting edits", self.name()))?;
+        let tree = apply_edits(tree, parser, source_code, &edits, range)?;
 
-                        tree = parser.parse(&source_code, Some(&tree)).expect("error re-parsing code.");
-                    }
-                }
-            } else {
-                break;
+        Ok((tree, changes(&edits)))
+    }
+}
+
+/// How many times the whole pipeline runs at most when nothing else is configured, the formatting of most files
+/// settles after one or two.
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+fn advance(point: Point, text: &[u8]) -> Point {
+    match text.iter().rposition(|byte| *byte == b'\n') {
+        Some(last) => Point { row: point.row + text.iter().filter(|byte| **byte == b'\n').count(), column: text.len() - last - 1 },
+        None => Point { row: point.row, column: point.column + text.len() },
+    }
+}
+
+/// Applies `edits`, sorted and not overlapping, to `source_code` at once and parses it again, reusing the unchanged
+/// parts of `tree`. `range` is moved along with the edits.
+fn apply_edits(mut tree: Tree, parser: &mut Parser, source_code: &mut Vec<u8>, edits: &[Edit], mut range: Option<&mut Range<usize>>) -> anyhow::Result<Tree> {
+    if edits.is_empty() {
+        return Ok(tree);
+    }
+
+    let index = LineIndex::new(source_code);
+    let mut result = Vec::with_capacity(source_code.len());
+    let mut copied = 0;
+
+    for edit in edits {
+        result.extend_from_slice(&source_code[copied..edit.position]);
+        result.extend_from_slice(&edit.inserted_text);
+        copied = edit.position + edit.deleted_length;
+    }
+
+    result.extend_from_slice(&source_code[copied..]);
+
+    // From the end backwards, so the positions of the edits still to tell the tree about are the original ones.
+    for edit in edits.iter().rev() {
+        let start_position = index.point(edit.position);
+
+        tree.edit(&InputEdit {
+            start_byte: edit.position,
+            old_end_byte: edit.position + edit.deleted_length,
+            new_end_byte: edit.position + edit.inserted_text.len(),
+            start_position,
+            old_end_position: index.point(edit.position + edit.deleted_length),
+            new_end_position: advance(start_position, &edit.inserted_text),
+        });
+
+        if let Some(range) = range.as_deref_mut() {
+            *range = shift_range(range, edit);
+        }
+    }
+
+    *source_code = result;
+
+    parser.parse(&source_code, Some(&tree)).context("Failed to parse source code.")
+}
+
+/// Sorts `edits`, failing when two of them overlap, including when one is nested inside the other: the inner one
+/// was made for code the outer one replaces, so they can't both be applied.
+fn non_overlapping(mut edits: Vec<Edit>) -> anyhow::Result<Vec<Edit>> {
+    // Outer edits first, so the ones nested inside them are always found right after them.
+    edits.sort_by_key(|edit| (edit.position, Reverse(edit.deleted_length)));
+
+    for pair in edits.windows(2) {
+        let (last, edit) = (&pair[0], &pair[1]);
+        let (last_end, end) = (last.position + last.deleted_length, edit.position + edit.deleted_length);
+
+        if edit.position < last_end || edit.position == last.position {
+            match end > last_end {
+                true => bail!("the edit of bytes {}..{} overlaps the edit of bytes {}..{}", edit.position, end, last.position, last_end),
+                false => bail!("the edit of bytes {}..{} is nested in the edit of bytes {}..{}", edit.position, end, last.position, last_end),
             }
         }
+    }
+
+    Ok(edits)
+}
+
+/// The indices of `fixers` sorted so that every fixer runs after the ones it depends on. Among the fixers whose
+/// dependencies have all run, the one with the highest priority goes first, then the one added first. Dependencies
+/// on fixers that are not enabled are ignored.
+fn run_order(fixers: &[Box<dyn Fixer>]) -> anyhow::Result<Vec<usize>> {
+    let index_of = |name: &str| fixers.iter().position(|fixer| fixer.name() == name);
+
+    // `after[index]` holds the fixers that have to run before the fixer at `index`.
+    let mut after: Vec<Vec<usize>> = vec![vec![]; fixers.len()];
 
-        Ok(tree)
+    for (index, fixer) in fixers.iter().enumerate() {
+        after[index].extend(fixer.runs_after().iter().filter_map(|name| index_of(name)));
+
+        for other in fixer.runs_before().iter().filter_map(|name| index_of(name)) {
+            after[other].push(index);
+        }
     }
+
+    let mut order: Vec<usize> = vec![];
+
+    while order.len() < fixers.len() {
+        let next = (0..fixers.len())
+            .filter(|index| !order.contains(index) && after[*index].iter().all(|other| order.contains(other)))
+            .min_by_key(|index| (Reverse(fixers[*index].priority()), *index));
+
+        let Some(next) = next else {
+            let names: Vec<&str> = (0..fixers.len()).filter(|index| !order.contains(index)).map(|index| fixers[index].name()).collect();
+
+            bail!("The fixers {} depend on each other in a cycle", names.join(", "));
+        };
+
+        order.push(next);
+    }
+
+    Ok(order)
+}
+
+/// The fixers named in `iterations`, each only once and in the order they first changed the code.
+fn involved(iterations: &[Vec<&str>]) -> String {
+    let mut names: Vec<&str> = vec![];
+
+    for name in iterations.iter().flatten() {
+        if !names.contains(name) {
+            names.push(name);
+        }
+    }
+
+    names.join(", ")
 }
 
 pub struct FixerRunner {
     fixers: Vec<Box<dyn Fixer>>,
+    /// The compiled query of every fixer, in the same order, compiled once on first use.
+    queries: Vec<Query>,
+    applied: Vec<String>,
+    syntax_errors: SyntaxErrors,
+    /// How many times the whole pipeline may run over the source code, not counting the last run confirming that
+    /// nothing changes anymore.
+    max_iterations: usize,
+}
+
+/// What to do with source code the parser could not make sense of.
+enum SyntaxErrors {
+    Refuse,
+    /// Only format the top-level statements that parsed cleanly.
+    FormatValidStatements,
+    /// Run the fixers anyway, only used by fixer tests, as their snippets aren't always complete PHP code.
+    Ignore,
 }
 
 impl FixerRunner {
     pub fn new() -> Self {
-        Self { fixers: vec![] }
+        Self { fixers: vec![], queries: vec![], applied: vec![], syntax_errors: SyntaxErrors::Refuse, max_iterations: DEFAULT_MAX_ITERATIONS }
+    }
+
+    /// Instead of refusing source code with syntax errors, only format its top-level statements that parsed cleanly.
+    pub fn allow_syntax_errors(&mut self, allow: bool) {
+        self.syntax_errors = match allow {
+            true => SyntaxErrors::FormatValidStatements,
+            false => SyntaxErrors::Refuse,
+        };
+    }
+
+    /// How many times the whole pipeline may run before formatting is given up on, see `DEFAULT_MAX_ITERATIONS`.
+    pub fn max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations.max(1);
     }
 
     pub fn add_fixer(&mut self, fixer: Box<dyn Fixer>) {
         self.fixers.push(fixer);
     }
 
-    pub fn execute(&mut self, source_code: &mut Vec<u8>) -> anyhow::Result<Tree> {
+    pub fn fixer_names(&self) -> Vec<&str> {
+        self.fixers.iter().map(|fixer| fixer.name()).collect()
+    }
+
+    /// The options changing what the fixers produce, e.g. to tell apart caches of runs with different options.
+    pub fn options(&self) -> String {
+        let allow_syntax_errors = matches!(self.syntax_errors, SyntaxErrors::FormatValidStatements);
+
+        format!("allow-syntax-errors={},max-iterations={}", allow_syntax_errors, self.max_iterations)
+    }
+
+    /// The fixers, in the order they run in once the queries have been compiled.
+    pub fn fixers(&self) -> impl Iterator<Item = &dyn Fixer> {
+        self.fixers.iter().map(|fixer| fixer.as_ref())
+    }
+
+    /// The names of the fixers that changed the source code during the last `execute`.
+    pub fn applied_fixers(&self) -> &[String] {
+        &self.applied
+    }
+
+    pub fn create_parser() -> anyhow::Result<Parser> {
         let mut parser = Parser::new();
-        let language = unsafe { tree_sitter_php() };
 
-        parser.set_language(language)?;
+        parser.set_language(unsafe { tree_sitter_php() })?;
+
+        Ok(parser)
+    }
 
-        let mut tree = parser.parse(&source_code, None)
-            .ok_or(anyhow::Error::msg("Failed to parse source code."))?;
+    pub fn parse(parser: &mut Parser, source_code: &[u8]) -> anyhow::Result<Tree> {
+        parser.parse(source_code, None).ok_or(anyhow::Error::msg("Failed to parse source code."))
+    }
 
-        for fixer in &mut self.fixers {
-            tree = fixer.execute(tree, &mut parser, source_code, &language)?;
+    /// Puts the fixers in the order they have to run in and compiles their queries, done on the first run when not
+    /// called before. Nothing is done again until another fixer is added.
+    pub fn compile_queries(&mut self, language: Language) -> anyhow::Result<()> {
+        if self.queries.len() == self.fixers.len() {
+            return Ok(());
         }
 
-        Ok(tree)
+        let order = run_order(&self.fixers)?;
+        let mut fixers: Vec<Option<Box<dyn Fixer>>> = self.fixers.drain(..).map(Some).collect();
+
+        self.fixers = order.into_iter().filter_map(|index| fixers[index].take()).collect();
+        self.queries = self.fixers.iter().map(|fixer| Query::new(language, fixer.query())).collect::<Result<_, _>>()?;
+
+        Ok(())
+    }
+
+    fn run(&mut self, mut tree: Tree, parser: &mut Parser, source_code: &mut Vec<u8>, range: Option<&Range<usize>>) -> anyhow::Result<Tree> {
+        self.compile_queries(tree.language())?;
+        self.applied.clear();
+
+        // The fixers expect a well formed tree, running them over `ERROR` or `MISSING` nodes may corrupt the code.
+        let valid = !tree.root_node().has_error();
+
+        match self.syntax_errors {
+            _ if valid => {}
+            SyntaxErrors::Refuse => ensure_valid(&tree, source_code)?,
+            SyntaxErrors::FormatValidStatements => return self.run_valid_statements(tree, parser, source_code, range),
+            SyntaxErrors::Ignore => {}
+        }
+
+        // The edits of every fixer move the code of the range along, it is kept up to date for the next iterations.
+        let mut range = range.cloned();
+
+        // Some fixers create work for others, e.g. the line breaks added by one change what another indents, so the
+        // whole pipeline runs again until it no longer changes anything. Every state is kept to tell the fixers
+        // undoing each other's changes apart from fixers that only need a few more iterations.
+        let mut states: Vec<Vec<u8>> = vec![source_code.clone()];
+        let mut applied_per_iteration: Vec<Vec<&'static str>> = vec![];
+
+        loop {
+            let applied;
+
+            (tree, applied) = self.run_once(tree, parser, source_code, range.as_mut(), valid)?;
+
+            for name in &applied {
+                if !self.applied.iter().any(|applied| applied == name) {
+                    self.applied.push(name.to_string());
+                }
+            }
+
+            if applied.is_empty() || states.last() == Some(source_code) {
+                return Ok(tree);
+            }
+
+            applied_per_iteration.push(applied);
+
+            if let Some(state) = states.iter().position(|state| state == source_code) {
+                bail!(
+                    "Formatting oscillates between {} states instead of settling, the fixers involved are {}",
+                    states.len() - state,
+                    involved(&applied_per_iteration[state..]),
+                );
+            }
+
+            if states.len() > self.max_iterations {
+                bail!(
+                    "Formatting did not settle after {} iterations, the fixers still changing the code are {}",
+                    self.max_iterations,
+                    involved(&applied_per_iteration[applied_per_iteration.len() - 1..]),
+                );
+            }
+
+            states.push(source_code.clone());
+        }
+    }
+
+    /// Runs every fixer once, in order, and returns the names of the ones that changed the source code.
+    fn run_once(&mut self, mut tree: Tree, parser: &mut Parser, source_code: &mut Vec<u8>, mut range: Option<&mut Range<usize>>, valid: bool) -> anyhow::Result<(Tree, Vec<&'static str>)> {
+        let mut applied = vec![];
+
+        for (fixer, query) in self.fixers.iter_mut().zip(&self.queries) {
+            let before = source_code.clone();
+            let before_tree = tree.clone();
+            let changes;
+
+            (tree, changes) = fixer.execute(tree, parser, source_code, query, range.as_deref_mut())?;
+
+            if changes.is_empty() {
+                continue;
+            }
+
+            // Whatever the fixer did to string literals, or to the regions protected by `@formatter` directives, is
+            // undone. Only flexible heredocs may move, along with the line they start on. Only the regions the changes
+            // reach into are looked at.
+            let strings = string_regions(&before_tree);
+            let mut regions = protected_regions(&before_tree, &before);
+
+            regions.extend(strings.iter().cloned());
+
+            let restored = restore(&before, &before_tree, source_code, &tree, &merge(regions), &changes)
+                .with_context(|| format!("{} changed code that has to be kept as is", fixer.name()))?;
+
+            tree = apply_edits(tree, parser, source_code, &restored, range.as_deref_mut())?;
+
+            if moves_heredocs(&before, &strings, &changes) {
+                let reindented = reindent_heredocs(&before, &before_tree, source_code, &tree);
+
+                tree = apply_edits(tree, parser, source_code, &reindented, range.as_deref_mut())?;
+            }
+
+            if *source_code == before {
+                continue;
+            }
+
+            applied.push(fixer.name());
+
+            // The tokens of code that does not even parse can't be trusted, so it is not checked.
+            if valid && !fixer.is_risky() {
+                ensure_equivalent(&before_tree, &before, &tree, source_code)
+                    .with_context(|| format!("{} changed the code instead of only its layout", fixer.name()))?;
+            }
+        }
+
+        Ok((tree, applied))
+    }
+
+    /// Formats every top-level statement that parsed cleanly on its own, as if it were a file of its own, and leaves
+    /// the rest of the source code as is.
+    fn run_valid_statements(&mut self, tree: Tree, parser: &mut Parser, source_code: &mut Vec<u8>, range: Option<&Range<usize>>) -> anyhow::Result<Tree> {
+        let root = tree.root_node();
+        let statements: Vec<Range<usize>> = root
+            .children(&mut root.walk())
+            .filter(|node| !node.has_error() && !matches!(node.kind(), "php_tag" | "text" | "text_interpolation"))
+            .map(|node| node.byte_range())
+            .filter(|statement| range.is_none_or(|range| intersects(statement, range)))
+            .collect();
+
+        let mut applied = vec![];
+
+        // From the end backwards, so the ranges of the statements still to format stay valid.
+        for statement in statements.into_iter().rev() {
+            let mut chunk = b"<?php\n".to_vec();
+
+            chunk.extend_from_slice(&source_code[statement.clone()]);
+
+            let chunk_tree = Self::parse(parser, &chunk)?;
+
+            // A statement may only be valid in its context, e.g. an `else` whose `if` is broken.
+            if chunk_tree.root_node().has_error() {
+                continue;
+            }
+
+            self.run(chunk_tree, parser, &mut chunk, None)?;
+
+            let formatted = chunk.strip_prefix(b"<?php").unwrap_or(&chunk).trim_ascii();
+
+            source_code.splice(statement, formatted.iter().copied());
+            applied.extend(self.applied.drain(..).filter(|name| !applied.contains(name)).collect::<Vec<_>>());
+        }
+
+        self.applied = applied;
+
+        Self::parse(parser, source_code)
+    }
+
+    pub fn execute(&mut self, source_code: &mut Vec<u8>) -> anyhow::Result<Tree> {
+        let mut parser = Self::create_parser()?;
+        let tree = Self::parse(&mut parser, source_code)?;
+
+        self.execute_parsed(&mut parser, tree, source_code)
+    }
+
+    /// Same as `execute` for source code already parsed into `tree`, `parser` is reused for every re-parse.
+    pub fn execute_parsed(&mut self, parser: &mut Parser, tree: Tree, source_code: &mut Vec<u8>) -> anyhow::Result<Tree> {
+        self.run(tree, parser, source_code, None)
+    }
+
+    /// Formats only the syntax nodes covering the byte `range`, every byte outside of the range is left as is.
+    pub fn execute_range(&mut self, source_code: &mut Vec<u8>, range: Range<usize>) -> anyhow::Result<Tree> {
+        let mut parser = Self::create_parser()?;
+        let tree = Self::parse(&mut parser, source_code)?;
+
+        self.execute_parsed_range(&mut parser, tree, source_code, range)
+    }
+
+    /// Same as `execute_range` for source code already parsed into `tree`.
+    pub fn execute_parsed_range(&mut self, parser: &mut Parser, tree: Tree, source_code: &mut Vec<u8>, range: Range<usize>) -> anyhow::Result<Tree> {
+        let covering = covering_range(&tree, source_code, &range);
+
+        let mut formatted = source_code.clone();
+        let formatted_tree = self.run(tree.clone(), parser, &mut formatted, Some(&covering))?;
+
+        *source_code = splice(source_code, &tree, &formatted, &formatted_tree, &range)?;
+
+        Self::parse(parser, source_code)
     }
 }
 
@@ -91,14 +511,259 @@ impl FixerTestRunner {
 
     pub fn assert(mut self) {
         let mut runner = FixerRunner {
-            fixers: self.fixers
+            fixers: self.fixers,
+            queries: vec![],
+            applied: vec![],
+            syntax_errors: SyntaxErrors::Ignore,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
         };
 
         runner.execute(&mut self.input).expect("Failed to execute fixers.");
 
-        let left = String::from_utf8(self.input).expect("Failed to convert input to string.");
+        let left = String::from_utf8(self.input.clone()).expect("Failed to convert input to string.");
         let right = String::from_utf8(self.output).expect("Failed to convert output to string.");
 
         assert_eq!(left, right);
+
+        // Formatting already formatted code must not change it any further.
+        runner.execute(&mut self.input).expect("Failed to execute fixers a second time.");
+
+        let again = String::from_utf8(self.input).expect("Failed to convert input to string.");
+
+        assert_eq!(again, left, "The fixers are not idempotent, a second pass changed the output.");
+    }
+}
+
+/// A runner with every fixer that is enabled by default, for the tests running the whole pipeline.
+#[cfg(test)]
+pub fn default_runner() -> FixerRunner {
+    let mut runner = FixerRunner::new();
+
+    crate::fixers::default_fixers().into_iter().for_each(|fixer| runner.add_fixer(fixer));
+
+    runner
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tree_sitter::Node;
+
+    use crate::fixer::{default_runner, Fixer, FixerRunner, non_overlapping, run_order};
+    use crate::test_utilities::Edit;
+
+    fn edit(position: usize, deleted_length: usize) -> Edit {
+        Edit { position, deleted_length, inserted_text: vec![] }
+    }
+
+    struct Ordered(&'static str, i32, &'static [&'static str]);
+
+    impl Fixer for Ordered {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn description(&self) -> &'static str {
+            "Only declares an order."
+        }
+
+        fn priority(&self) -> i32 {
+            self.1
+        }
+
+        fn runs_after(&self) -> &'static [&'static str] {
+            self.2
+        }
+
+        fn query(&self) -> &str {
+            "(program) @program"
+        }
+
+        fn fix(&mut self, _: &Node, _: &[u8]) -> Option<Edit> {
+            None
+        }
+    }
+
+    #[test]
+    fn it_rejects_overlapping_and_nested_edits() {
+        let edits = non_overlapping(vec![edit(10, 2), edit(0, 8), edit(8, 0)]).unwrap();
+
+        assert_eq!(edits.iter().map(|edit| edit.position).collect::<Vec<_>>(), vec![0, 8, 10]);
+
+        let error = non_overlapping(vec![edit(0, 8), edit(4, 8)]).unwrap_err();
+
+        assert_eq!(error.to_string(), "the edit of bytes 4..12 overlaps the edit of bytes 0..8");
+
+        let error = non_overlapping(vec![edit(10, 2), edit(0, 8), edit(2, 3)]).unwrap_err();
+
+        assert_eq!(error.to_string(), "the edit of bytes 2..5 is nested in the edit of bytes 0..8");
+    }
+
+    /// Rewrites the whole program and, separately, its first statement.
+    struct Nested;
+
+    impl Fixer for Nested {
+        fn name(&self) -> &'static str {
+            "nested"
+        }
+
+        fn description(&self) -> &'static str {
+            "Produces nested edits."
+        }
+
+        fn query(&self) -> &str {
+            "(program) @program"
+        }
+
+        fn fixes(&mut self, node: &Node, _: &[u8]) -> Vec<Edit> {
+            vec![
+                Edit { position: node.start_byte(), deleted_length: node.byte_range().len(), inserted_text: b"<?php\n$a;\n$b;\n".to_vec() },
+                Edit { position: 6, deleted_length: 3, inserted_text: b"$b;".to_vec() },
+            ]
+        }
+    }
+
+    #[test]
+    fn it_reports_the_fixer_producing_conflicting_edits() {
+        let mut runner = FixerRunner::new();
+        let mut source_code = b"<?php\n$a;\n".to_vec();
+
+        runner.add_fixer(Box::new(Nested));
+
+        assert_eq!(
+            format!("{:#}", runner.execute(&mut source_code).unwrap_err()),
+            "nested produced conflicting edits: the edit of bytes 6..9 is nested in the edit of bytes 0..10",
+        );
+    }
+
+    #[test]
+    fn it_orders_fixers_by_dependencies_then_priority() {
+        let fixers: Vec<Box<dyn Fixer>> = vec![
+            Box::new(Ordered("a", 0, &["c"])),
+            Box::new(Ordered("b", 0, &[])),
+            Box::new(Ordered("c", 0, &["missing"])),
+            Box::new(Ordered("d", 10, &[])),
+        ];
+
+        assert_eq!(run_order(&fixers).unwrap(), vec![3, 1, 2, 0]);
+
+        let fixers: Vec<Box<dyn Fixer>> = vec![
+            Box::new(Ordered("a", 0, &["b"])),
+            Box::new(Ordered("b", 0, &["a"])),
+            Box::new(Ordered("c", 0, &[])),
+        ];
+
+        assert_eq!(run_order(&fixers).unwrap_err().to_string(), "The fixers a, b depend on each other in a cycle");
+    }
+
+    /// Renames the variables `$from` to `$to`, or swaps them.
+    struct Rename(&'static str, &'static [&'static str], &'static str, &'static str, bool);
+
+    impl Fixer for Rename {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn description(&self) -> &'static str {
+            "Renames a variable."
+        }
+
+        fn runs_after(&self) -> &'static [&'static str] {
+            self.1
+        }
+
+        fn is_risky(&self) -> bool {
+            true
+        }
+
+        fn query(&self) -> &str {
+            "(variable_name) @variable"
+        }
+
+        fn fix(&mut self, node: &Node, source_code: &[u8]) -> Option<Edit> {
+            let inserted_text = match &source_code[node.byte_range()] {
+                text if text == self.2.as_bytes() => self.3,
+                text if text == self.3.as_bytes() && self.4 => self.2,
+                _ => return None,
+            };
+
+            Some(Edit { position: node.start_byte(), deleted_length: node.byte_range().len(), inserted_text: inserted_text.as_bytes().to_vec() })
+        }
+    }
+
+    #[test]
+    fn it_runs_the_fixers_again_until_nothing_changes() {
+        let mut runner = FixerRunner::new();
+        let mut source_code = b"<?php\n$c;\n".to_vec();
+
+        runner.add_fixer(Box::new(Rename("b_to_a", &[], "$b", "$a", false)));
+        runner.add_fixer(Box::new(Rename("c_to_b", &["b_to_a"], "$c", "$b", false)));
+        runner.execute(&mut source_code).unwrap();
+
+        assert_eq!(source_code, b"<?php\n$a;\n");
+        assert_eq!(runner.applied_fixers(), ["c_to_b", "b_to_a"]);
+
+        let mut source_code = b"<?php\n$c;\n".to_vec();
+
+        runner.max_iterations(1);
+
+        assert_eq!(
+            runner.execute(&mut source_code).unwrap_err().to_string(),
+            "Formatting did not settle after 1 iterations, the fixers still changing the code are b_to_a",
+        );
+    }
+
+    #[test]
+    fn it_reports_the_fixers_undoing_each_others_changes() {
+        let mut runner = FixerRunner::new();
+        let mut source_code = b"<?php\n$a;\n$c;\n".to_vec();
+
+        runner.add_fixer(Box::new(Rename("swap", &[], "$a", "$b", true)));
+        runner.add_fixer(Box::new(Rename("c_to_d", &[], "$c", "$d", false)));
+
+        assert_eq!(
+            runner.execute(&mut source_code).unwrap_err().to_string(),
+            "Formatting oscillates between 2 states instead of settling, the fixers involved are swap",
+        );
+    }
+
+    #[test]
+    fn it_moves_the_range_along_with_the_edits_of_every_fixer() {
+        let mut runner = FixerRunner::new();
+        let mut parser = FixerRunner::create_parser().unwrap();
+        let mut source_code = b"<?php\n$a + $b;\n$b;\n".to_vec();
+        let tree = FixerRunner::parse(&mut parser, &source_code).unwrap();
+
+        runner.add_fixer(Box::new(Rename("grow", &[], "$a", "$aaaaaaaaaa", false)));
+        runner.add_fixer(Box::new(Rename("b_to_c", &["grow"], "$b", "$c", false)));
+        runner.run(tree, &mut parser, &mut source_code, Some(&(6..14))).unwrap();
+
+        assert_eq!(String::from_utf8(source_code).unwrap(), "<?php\n$aaaaaaaaaa + $c;\n$b;\n");
+    }
+
+    fn time_formatting(lines: usize) -> Duration {
+        let mut source_code = b"<?php\n".to_vec();
+
+        (0..lines).for_each(|line| source_code.extend_from_slice(format!("$a{}=foo( 'x',$b );\n", line).as_bytes()));
+
+        let start = Instant::now();
+
+        default_runner().execute(&mut source_code).unwrap();
+
+        start.elapsed()
+    }
+
+    #[test]
+    #[ignore = "timing comparison, run it with --ignored --nocapture"]
+    fn it_formats_large_files_in_linear_time() {
+        let small = time_formatting(1000);
+        let large = time_formatting(4000);
+
+        println!("1000 lines formatted in {:?}, 4000 lines in {:?}", small, large);
+
+        // Four times the lines should take about four times as long, work growing with the square of the size of the
+        // file, e.g. looking at every string for every edit, makes it take sixteen times as long.
+        assert!(large < small * 8, "4000 lines took {:?}, 1000 lines {:?}", large, small);
     }
 }
@@ -1,44 +1,42 @@
 use tree_sitter::Node;
 
 use crate::fixer::Fixer;
-use crate::test_utilities::Edit;
+use crate::test_utilities::{Edit, space_children};
 
 pub struct ArrayBracketSpaceFixer {}
 
 impl Fixer for ArrayBracketSpaceFixer {
+    fn name(&self) -> &'static str {
+        "array_bracket_space"
+    }
+
+    fn description(&self) -> &'static str {
+        "Puts a single space inside the brackets of arrays and after each of their commas."
+    }
+
+    fn runs_after(&self) -> &'static [&'static str] {
+        &["normalizer"]
+    }
+
     fn query(&self) -> &str {
         "(array_creation_expression) @value"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
-        let tokens: Vec<u8> = node
-            .children(&mut node.walk())
-            .map(|child| match child.kind() {
-                "[" => {
-                    if let Some(next) = child.next_sibling() {
-                        if next.kind() == "]" { return "[".as_bytes(); }
-                    }
-                    "[ ".as_bytes()
-                }
-                "]" => {
-                    if let Some(next) = child.prev_sibling() {
-                        if next.kind() == "[" { return "]".as_bytes(); }
-                    }
-                    " ]".as_bytes()
-                }
-                "," => ", ".as_bytes(),
-                _ => &source_code[child.byte_range()]
-            })
-            .flat_map(|token| token.to_owned())
-            .collect();
-
-        Some(
-            Edit {
-                deleted_length: node.end_byte() - node.start_byte(),
-                position: node.start_byte(),
-                inserted_text: tokens,
-            }
-        )
+    fn fixes(&mut self, node: &Node, _: &[u8]) -> Vec<Edit> {
+        space_children(node, |previous, next| {
+            let after = match previous.kind() {
+                "[" if next.kind() == "]" => "",
+                "[" | "," => " ",
+                _ => "",
+            };
+
+            let before = match next.kind() {
+                "]" if previous.kind() != "[" => " ",
+                _ => "",
+            };
+
+            [after, before].concat().into_bytes()
+        })
     }
 }
 
@@ -8,11 +8,27 @@ pub struct DeclareDirectiveExistenceFixer {}
 impl DeclareDirectiveExistenceFixer {}
 
 impl Fixer for DeclareDirectiveExistenceFixer {
+    fn name(&self) -> &'static str {
+        "declare_strict_types"
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds `declare(strict_types = 1);` to files that do not declare it."
+    }
+
+    fn runs_before(&self) -> &'static [&'static str] {
+        &["declare_directive_space", "header_line"]
+    }
+
+    fn is_risky(&self) -> bool {
+        true
+    }
+
     fn query(&self) -> &str {
         "(php_tag) @tag"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
+    fn fix(&mut self, node: &Node, _source_code: &[u8]) -> Option<Edit> {
         let token = Vec::from("<?php declare(strict_types = 1);");
 
         let edit = Edit {
@@ -1,31 +1,31 @@
-use std::ops::Deref;
-
 use tree_sitter::Node;
 
 use crate::fixer::Fixer;
-use crate::test_utilities::Edit;
+use crate::test_utilities::{Edit, space_children};
 
 pub struct DeclareDirectiveSpaceFixer {}
 
 impl Fixer for DeclareDirectiveSpaceFixer {
+    fn name(&self) -> &'static str {
+        "declare_directive_space"
+    }
+
+    fn description(&self) -> &'static str {
+        "Puts a single space around the `=` of declare directives."
+    }
+
+    fn runs_after(&self) -> &'static [&'static str] {
+        &["normalizer"]
+    }
+
     fn query(&self) -> &str {
         "(declare_statement (declare_directive) @fix-equal) @fix-parenthesis"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
-        let tokens: Vec<u8> = node
-            .children(&mut node.walk())
-            .map(|child| match child.kind() {
-                "=" => b" = ",
-                _ => &source_code[child.byte_range()]
-            })
-            .flat_map(|token| token.to_owned())
-            .collect();
-
-        Some(Edit {
-            deleted_length: node.end_byte() - node.start_byte(),
-            position: node.start_byte(),
-            inserted_text: tokens,
+    fn fixes(&mut self, node: &Node, _: &[u8]) -> Vec<Edit> {
+        space_children(node, |previous, next| match (previous.kind(), next.kind()) {
+            ("=", _) | (_, "=") => b" ".to_vec(),
+            _ => vec![],
         })
     }
 }
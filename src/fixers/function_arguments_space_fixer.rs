@@ -1,32 +1,32 @@
 use tree_sitter::Node;
 
 use crate::fixer::Fixer;
-use crate::test_utilities::Edit;
+use crate::test_utilities::{Edit, space_children};
 
 pub struct FunctionArgumentsSpaceFixer {}
 
 impl Fixer for FunctionArgumentsSpaceFixer {
+    fn name(&self) -> &'static str {
+        "function_arguments_space"
+    }
+
+    fn description(&self) -> &'static str {
+        "Puts a single space after each comma of the arguments of function calls."
+    }
+
+    fn runs_after(&self) -> &'static [&'static str] {
+        &["normalizer"]
+    }
+
     fn query(&self) -> &str {
         "(function_call_expression arguments: (arguments) @arguments)"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
-        let tokens: Vec<u8> = node
-            .children(&mut node.walk())
-            .map(|child| match child.kind() {
-                "," => b", ",
-                _ => &source_code[child.byte_range()]
-            })
-            .flat_map(|token| token.to_owned())
-            .collect();
-
-        Some(
-            Edit {
-                deleted_length: node.end_byte() - node.start_byte(),
-                position: node.start_byte(),
-                inserted_text: tokens,
-            }
-        )
+    fn fixes(&mut self, node: &Node, _: &[u8]) -> Vec<Edit> {
+        space_children(node, |previous, _| match previous.kind() {
+            "," => b" ".to_vec(),
+            _ => vec![],
+        })
     }
 }
 
@@ -57,4 +57,19 @@ mod tests {
 
         assert_inputs(input, output);
     }
+
+    #[test]
+    fn it_add_spaces_between_the_arguments_of_nested_calls() {
+        let input = indoc! {"
+        <?php
+        foo(bar(1,2),3);
+        "};
+
+        let output = indoc! {"
+        <?php
+        foo(bar(1, 2), 3);
+        "};
+
+        assert_inputs(input, output);
+    }
 }
@@ -7,7 +7,7 @@ use crate::test_utilities::Edit;
 pub struct HeaderLineFixer {}
 
 impl HeaderLineFixer {
-    fn handle_ungrouped(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_ungrouped(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut tokens = source_code[node.byte_range()].to_vec();
 
         tokens.extend_from_slice(LINE_BREAK);
@@ -19,7 +19,7 @@ impl HeaderLineFixer {
         tokens
     }
 
-    fn handle_grouped(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_grouped(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut tokens = source_code[node.byte_range()].to_vec();
 
         tokens.extend_from_slice(LINE_BREAK);
@@ -32,7 +32,7 @@ impl HeaderLineFixer {
         tokens
     }
 
-    fn process(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn process(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         node.children(&mut node.walk())
             .map(|child| match child.kind() {
                 "php_tag" |
@@ -50,16 +50,28 @@ impl HeaderLineFixer {
 }
 
 impl Fixer for HeaderLineFixer {
+    fn name(&self) -> &'static str {
+        "header_line"
+    }
+
+    fn description(&self) -> &'static str {
+        "Separates the opening tag, declare statements, namespace and imports of a file with blank lines."
+    }
+
+    fn runs_after(&self) -> &'static [&'static str] {
+        &["normalizer"]
+    }
+
     fn query(&self) -> &str {
         "(program) @program"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
+    fn fix(&mut self, node: &Node, source_code: &[u8]) -> Option<Edit> {
         Some(
             Edit {
                 deleted_length: node.end_byte() - node.start_byte(),
                 position: node.start_byte(),
-                inserted_text: self.process(&node, source_code),
+                inserted_text: self.process(node, source_code),
             }
         )
     }
@@ -1,9 +1,6 @@
-use std::ops::Sub;
-
-use anyhow::Context;
 use tree_sitter::Node;
 
-use crate::constants::{INDENT, INDENT_SIZE, INDENT_STR, LINE_BREAK, LINE_BREAK_STR};
+use crate::constants::{INDENT, INDENT_STR, LINE_BREAK, LINE_BREAK_STR};
 use crate::fixer::Fixer;
 use crate::test_utilities::Edit;
 
@@ -15,7 +12,7 @@ impl IndentBracketBodyFixer {
         node: &Node,
         parent: &Node,
         current_indent: &mut Vec<u8>,
-        source_code: &Vec<u8>,
+        source_code: &[u8],
         level: usize,
     )
     {
@@ -30,8 +27,8 @@ impl IndentBracketBodyFixer {
             }
         }
 
-        let mut inner_edit = self.process(&node, source_code, level, nesting);
-        let mut indent_level = indent_size * level;
+        let mut inner_edit = self.process(node, source_code, level, nesting);
+        let indent_level = indent_size * level;
         let mut sub_indent_by = 0;
 
         if let Some(previous_node) = node.prev_sibling() {
@@ -40,15 +37,13 @@ impl IndentBracketBodyFixer {
             let difference = node_start_byte - previous_node_end_byte;
             let is_over_indented = node_start_byte > previous_node_end_byte + indent_level;
 
-            if is_over_indented == true {
+            if is_over_indented {
                 sub_indent_by = difference - indent_level - line_break_size;
             }
 
-            if is_over_indented == false {
+            if !is_over_indented {
                 //----------------------------------------------------------------------------------
-                let repeat_by = (indent_level + line_break_size)
-                    .checked_sub(difference)
-                    .unwrap_or(0);
+                let repeat_by = (indent_level + line_break_size).saturating_sub(difference);
 
                 let mut indent = b" ".repeat(repeat_by % indent_level);
 
@@ -66,7 +61,7 @@ impl IndentBracketBodyFixer {
         current_indent.splice(start_offset..=end_offset, inner_edit);
     }
 
-    fn handle_switch_block<'a>(&self, node: Node<'a>) -> Option<Vec<Node<'a>>> {
+    fn handle_switch_block<'a>(&self, _node: Node<'a>) -> Option<Vec<Node<'a>>> {
         // todo
         // maybe is better to crash the software to teach a lesson to the users who uses switch statement
         None
@@ -81,7 +76,7 @@ impl IndentBracketBodyFixer {
                 .flatten()
                 .collect();
 
-            if collection.is_empty() == false {
+            if !collection.is_empty() {
                 return Some(collection)
             }
         }
@@ -99,22 +94,18 @@ impl IndentBracketBodyFixer {
 
     fn handle_default<'a>(&self, node: Node<'a>) -> Option<Vec<Node<'a>>> {
         node.child_by_field_name("body")
-            .filter(|node| match node.kind() {
-                "compound_statement" => true,
-                "match_block" => true,
-                _ => false
-            })
+            .filter(|node| matches!(node.kind(), "compound_statement" | "match_block"))
             .map(|node| vec![node])
     }
 
-    fn handle_node(&self, child: &Node, source_code: &Vec<u8>, level: usize) -> Vec<u8> {
+    fn handle_node(&self, child: &Node, source_code: &[u8], level: usize) -> Vec<u8> {
         let mut tokens = source_code[child.byte_range()].to_vec();
         let current_level = level + 1;
 
         let mut indent = INDENT.repeat(current_level).to_vec();
         indent.append(&mut tokens);
 
-        if let Some(_) = child.next_sibling().filter(|node| node.kind() != ",") {
+        if child.next_sibling().filter(|node| node.kind() != ",").is_some() {
             indent.extend_from_slice(LINE_BREAK);
         }
 
@@ -134,7 +125,7 @@ impl IndentBracketBodyFixer {
                 for inner_child in inner_children {
                     //------------------------------------------------------------------------------
                     self.indent_compound_statement_node(
-                        &inner_child, &child, &mut indent, source_code, current_level
+                        &inner_child, child, &mut indent, source_code, current_level
                     );
                     //------------------------------------------------------------------------------
                 }
@@ -146,7 +137,7 @@ impl IndentBracketBodyFixer {
         indent
     }
 
-    fn process(&self, node: &Node, source_code: &Vec<u8>, level: usize, nesting: usize) -> Vec<u8> {
+    fn process(&self, node: &Node, source_code: &[u8], level: usize, nesting: usize) -> Vec<u8> {
         node.children(&mut node.walk())
             .map(|child| match child.kind() {
                 "{" => {
@@ -170,16 +161,28 @@ impl IndentBracketBodyFixer {
 }
 
 impl Fixer for IndentBracketBodyFixer {
+    fn name(&self) -> &'static str {
+        "indent_bracket_body"
+    }
+
+    fn description(&self) -> &'static str {
+        "Indents the body of classes."
+    }
+
+    fn runs_after(&self) -> &'static [&'static str] {
+        &["normalizer"]
+    }
+
     fn query(&self) -> &str {
         "(class_declaration body: (declaration_list) @brackets)"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
+    fn fix(&mut self, node: &Node, source_code: &[u8]) -> Option<Edit> {
         Some(
             Edit {
                 deleted_length: node.end_byte() - node.start_byte(),
                 position: node.start_byte(),
-                inserted_text: self.process(&node, source_code, 0, 0),
+                inserted_text: self.process(node, source_code, 0, 0),
             }
         )
     }
@@ -1,6 +1,3 @@
-use std::ops::Sub;
-
-use anyhow::Context;
 use tree_sitter::{Node, Point};
 
 use crate::constants::{INDENT, INDENT_SIZE, LINE_BREAK};
@@ -29,34 +26,6 @@ impl MemberExpression {
 }
 
 impl IndentChainedCallFixer {
-    fn handle_node(&self, child: &Node, source_code: &Vec<u8>, level: usize) -> Vec<u8> {
-        let mut tokens = source_code[child.byte_range()].to_vec();
-
-        let is_multiline = child.start_position().row != child.end_position().row;
-
-        // ensure everything is multiline
-        if is_multiline {}
-
-        for child in child.named_children(&mut child.walk()) {
-            if child.kind() == "member_call_expression" {
-                // self.handle_node(&child, source_code, level);
-            }
-        }
-
-        println!("{:?}", String::from_utf8(tokens.clone()).unwrap());
-
-        // let current_level = level + 1;
-        //
-        // let mut indent = indent.repeat(current_level).to_vec();
-        // indent.append(&mut tokens);
-        //
-        // if let Some(_) = child.next_sibling().filter(|node| node.kind() != ",") {
-        //     indent.extend_from_slice(LINE_BREAK);
-        // }
-
-        tokens
-    }
-
     fn count_chain(&self, node: &Node) -> usize {
         node.children(&mut node.walk())
             .fold(1, |count, child| match child.kind() {
@@ -67,7 +36,7 @@ impl IndentChainedCallFixer {
 
     fn get_expressions(&self, node: &Node) -> Vec<MemberExpression> {
         node.children(&mut node.walk())
-            .fold(vec![MemberExpression::new(&node)], |count, child| match child.kind() {
+            .fold(vec![MemberExpression::new(node)], |count, child| match child.kind() {
                 "member_call_expression" => {
                     let mut response = self.get_expressions(&child);
                     response.push(MemberExpression::new(&child));
@@ -77,7 +46,7 @@ impl IndentChainedCallFixer {
             })
     }
 
-    fn process(&self, node: &Node, source_code: &Vec<u8>, is_root: bool, member_count: usize, child_id: usize) -> Vec<u8> {
+    fn process(&self, node: &Node, source_code: &[u8], is_root: bool, member_count: usize, child_id: usize) -> Vec<u8> {
         if member_count < 3 {
             return node.children(&mut node.walk())
                 .map(|child| match child.kind() {
@@ -95,9 +64,9 @@ impl IndentChainedCallFixer {
         // }
 
         let start = node.start_position().column;
-        let current_level = (INDENT.len() % start).checked_sub(1).unwrap_or(0);
+        let current_level = (INDENT.len() % start).saturating_sub(1);
 
-        let mut response: Vec<u8> = node.children(&mut node.walk())
+        let response: Vec<u8> = node.children(&mut node.walk())
             .map(|child| match child.kind() {
                 "->" => {
                     let mut indent = LINE_BREAK.as_slice().to_vec();
@@ -105,9 +74,6 @@ impl IndentChainedCallFixer {
                     indent.append(&mut INDENT.repeat(current_level).to_vec());
                     indent.extend_from_slice(&source_code[child.byte_range()]);
 
-                    let start = child.prev_sibling().unwrap().start_byte();
-                    let root_start = node.start_byte();
-
                     if child.next_named_sibling().is_none() {
                         indent.extend_from_slice(LINE_BREAK);
                     }
@@ -116,7 +82,7 @@ impl IndentChainedCallFixer {
                 }
                 "member_call_expression" => self.process(&child, source_code, false, member_count, child_id - 1),
                 _ => {
-                    let mut tokens = source_code[child.byte_range()].to_vec();
+                    let tokens = source_code[child.byte_range()].to_vec();
 
                     if is_root {
                         // println!("PREVI {:?}", node.parent())
@@ -161,7 +127,7 @@ impl IndentChainedCallFixer {
         false
     }
 
-    fn process_children(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn process_children(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         node.children(&mut node.walk())
             .map(|child| match child.kind() {
                 "member_call_expression" => self.process_children(&child, source_code),
@@ -189,7 +155,7 @@ impl IndentChainedCallFixer {
         parent.start_position().column
     }
 
-    fn process_root(&self, node: &Node, source_code: &Vec<u8>, length: usize) -> Vec<u8> {
+    fn process_root(&self, node: &Node, source_code: &[u8], length: usize) -> Vec<u8> {
         if length <= 3 {
             return self.process_children(node, source_code);
         }
@@ -216,12 +182,24 @@ impl IndentChainedCallFixer {
 }
 
 impl Fixer for IndentChainedCallFixer {
+    fn name(&self) -> &'static str {
+        "indent_chained_call"
+    }
+
+    fn description(&self) -> &'static str {
+        "Puts each call of long method chains on its own indented line."
+    }
+
+    fn runs_after(&self) -> &'static [&'static str] {
+        &["normalizer", "indent_bracket_body"]
+    }
+
     fn query(&self) -> &str {
         "(member_call_expression) @chain"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
-        if self.is_root_expression(node) == false {
+    fn fix(&mut self, node: &Node, source_code: &[u8]) -> Option<Edit> {
+        if !self.is_root_expression(node) {
             return None;
         }
 
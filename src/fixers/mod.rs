@@ -7,3 +7,21 @@ pub mod function_arguments_space_fixer;
 pub mod indent_bracket_body_fixer;
 pub mod indent_chained_call_fixer;
 pub mod normalizer_fixer;
+
+use crate::fixer::Fixer;
+use crate::fixers::normalizer_fixer::NormalizerFixer;
+
+/// Creates a fresh instance of every fixer that is enabled by default, `FixerRunner` sorts them into the order they
+/// have to run in.
+pub fn default_fixers() -> Vec<Box<dyn Fixer>> {
+    vec![
+        Box::new(NormalizerFixer {}),
+        // Box::new(array_bracket_space_fixer::ArrayBracketSpaceFixer {}),
+        // Box::new(declare_directive_space_fixer::DeclareDirectiveSpaceFixer {}),
+        // Box::new(declare_directive_existence_fixer::DeclareDirectiveExistenceFixer {}),
+        // Box::new(function_arguments_space_fixer::FunctionArgumentsSpaceFixer {}),
+        // Box::new(indent_bracket_body_fixer::IndentBracketBodyFixer {}),
+        // Box::new(indent_chained_call_fixer::IndentChainedCallFixer {}),
+        // Box::new(header_line_fixer::HeaderLineFixer {}),
+    ]
+}
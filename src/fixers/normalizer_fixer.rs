@@ -2,6 +2,7 @@ use tree_sitter::Node;
 use crate::constants::LINE_BREAK;
 
 use crate::fixer::Fixer;
+use crate::strings::is_string_like;
 use crate::test_utilities::Edit;
 
 enum Sequence {
@@ -18,25 +19,21 @@ pub struct NormalizerFixer {}
 
 impl NormalizerFixer {
     fn get_node_sequence<'a>(&self, node: &'a Node, sequence: &[Sequence]) -> Option<Node<'a>> {
-        sequence.iter().fold(Some(*node), |node, sequence| {
-            if let Some(node) = node {
-                match sequence {
-                    Sequence::Parent => node.parent(),
-                    Sequence::Next => node.next_sibling(),
-                    Sequence::NextNamed => node.next_named_sibling(),
-                    Sequence::Previous => node.prev_sibling(),
-                    Sequence::PreviousNamed => node.prev_named_sibling(),
-                    Sequence::NextIsNoneParent => match node.next_sibling() {
-                        None => node.parent(),
-                        Some(_) => None,
-                    },
-                    Sequence::PreviousIsNoneParent => match node.prev_sibling() {
-                        None => node.parent(),
-                        Some(_) => None,
-                    },
-                }
-            } else {
-                None
+        sequence.iter().try_fold(*node, |node, sequence| {
+            match sequence {
+                Sequence::Parent => node.parent(),
+                Sequence::Next => node.next_sibling(),
+                Sequence::NextNamed => node.next_named_sibling(),
+                Sequence::Previous => node.prev_sibling(),
+                Sequence::PreviousNamed => node.prev_named_sibling(),
+                Sequence::NextIsNoneParent => match node.next_sibling() {
+                    None => node.parent(),
+                    Some(_) => None,
+                },
+                Sequence::PreviousIsNoneParent => match node.prev_sibling() {
+                    None => node.parent(),
+                    Some(_) => None,
+                },
             }
         })
     }
@@ -97,7 +94,7 @@ impl NormalizerFixer {
 }
 
 impl NormalizerFixer {
-    fn line_break_before_and_after(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn line_break_before_and_after(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut line_break = LINE_BREAK.to_vec();
         line_break.extend_from_slice(&source_code[node.byte_range()]);
         line_break.extend_from_slice(LINE_BREAK);
@@ -105,14 +102,14 @@ impl NormalizerFixer {
         line_break
     }
 
-    fn line_break_before(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn line_break_before(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut line_break = LINE_BREAK.to_vec();
         line_break.extend_from_slice(&source_code[node.byte_range()]);
 
         line_break
     }
 
-    fn space_before_and_after(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn space_before_and_after(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut tokens = b" ".to_vec();
         tokens.extend_from_slice(&source_code[node.byte_range()]);
         tokens.extend_from_slice(b" ");
@@ -120,7 +117,7 @@ impl NormalizerFixer {
         tokens
     }
 
-    fn line_break_before_and_space_after(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn line_break_before_and_space_after(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut line_break = LINE_BREAK.to_vec();
         line_break.extend_from_slice(&source_code[node.byte_range()]);
         line_break.extend_from_slice(b" ");
@@ -128,21 +125,21 @@ impl NormalizerFixer {
         line_break
     }
 
-    fn line_break_after(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn line_break_after(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut tokens = source_code[node.byte_range()].to_vec();
         tokens.extend_from_slice(LINE_BREAK);
 
         tokens
     }
 
-    fn space_before(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn space_before(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut tokens = b" ".to_vec();
         tokens.extend_from_slice(&source_code[node.byte_range()]);
 
         tokens
     }
 
-    fn space_after(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn space_after(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let mut tokens = source_code[node.byte_range()].to_vec();
 
         tokens.extend_from_slice(b" ");
@@ -150,116 +147,116 @@ impl NormalizerFixer {
         tokens
     }
 
-    fn pass_through(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn pass_through(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         source_code[node.byte_range()].to_vec()
     }
 }
 
 impl NormalizerFixer {
-    fn handle_return(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_return(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(next) = node.next_sibling() {
             if next.kind() == ";" {
-                return self.pass_through(&node, &source_code);
+                return self.pass_through(node, source_code);
             }
         }
 
-        self.space_after(&node, &source_code)
+        self.space_after(node, source_code)
     }
 
-    fn handle_semicolon(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_semicolon(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(next) = node.next_sibling() {
             if next.kind() == ")" {
-                return self.pass_through(&node, &source_code);
+                return self.pass_through(node, source_code);
             }
         }
 
-        self.line_break_after(&node, &source_code)
+        self.line_break_after(node, source_code)
     }
 
-    fn handle_class_kind(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_class_kind(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(previous) = node.prev_sibling() {
             if previous.kind() == "abstract_modifier" {
-                return self.space_before_and_after(&node, &source_code);
+                return self.space_before_and_after(node, source_code);
             }
         }
 
-        self.space_after(&node, &source_code)
+        self.space_after(node, source_code)
     }
 
-    fn handle_open_parenthesis(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_open_parenthesis(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(next) = node.next_sibling() {
             if next.kind() == ")" {
-                return self.pass_through(&node, &source_code)
+                return self.pass_through(node, source_code)
             }
         }
 
-        self.line_break_after(&node, &source_code)
+        self.line_break_after(node, source_code)
     }
 
-    fn handle_close_parenthesis(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_close_parenthesis(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(previous) = node.prev_sibling() {
             // if previous.kind() == "argument" && node.parent().unwrap().kind() == "formal_parameters" {
             //     return self.pass_through(&node, &source_code);
             // }
 
             return match previous.kind() {
-                "," | "(" => self.pass_through(&node, &source_code),
-                _ => self.line_break_before(&node, &source_code),
+                "," | "(" => self.pass_through(node, source_code),
+                _ => self.line_break_before(node, source_code),
             };
         }
 
-        self.line_break_before(&node, &source_code)
+        self.line_break_before(node, source_code)
     }
 
-    fn handle_close_squiggly_bracket(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_close_squiggly_bracket(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(parent) = node.parent() {
             if let Some(parent) = parent.parent() {
                 if let Some(next) = parent.next_sibling() {
                     if next.kind() != ";" {
-                        return self.line_break_after(&node, &source_code);
+                        return self.line_break_after(node, source_code);
                     }
                 }
 
                 if parent.kind() == "anonymous_function_creation_expression" {
-                    return self.pass_through(&node, &source_code);
+                    return self.pass_through(node, source_code);
                 }
             }
 
             if parent.kind() == "declaration_list" {
-                return self.pass_through(&node, &source_code);
+                return self.pass_through(node, source_code);
             }
         }
 
         match node.next_sibling() {
-            None => self.line_break_after(&node, &source_code),
-            Some(_) => self.pass_through(&node, &source_code)
+            None => self.line_break_after(node, source_code),
+            Some(_) => self.pass_through(node, source_code)
         }
     }
 
-    fn handle_open_array_bracket(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_open_array_bracket(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(next) = node.next_sibling() {
             if next.kind() == "]" {
-                return self.pass_through(&node, &source_code);
+                return self.pass_through(node, source_code);
             }
         }
 
         if let Some(previous) = node.prev_sibling() {
             if ["variable_name", "member_access_expression"].contains(&previous.kind()) {
-                return self.space_after(&node, &source_code);
+                return self.space_after(node, source_code);
             }
         }
 
-        self.line_break_after(&node, &source_code)
+        self.line_break_after(node, source_code)
     }
 
-    fn handle_close_array_bracket(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
-        if self.parent_is(&node, "attribute_group") {
-            return self.line_break_after(&node, &source_code);
+    fn handle_close_array_bracket(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
+        if self.parent_is(node, "attribute_group") {
+            return self.line_break_after(node, source_code);
         }
 
         if let Some(previous) = node.prev_sibling() {
             if ["[", ","].contains(&previous.kind()) {
-                return self.pass_through(&node, &source_code);
+                return self.pass_through(node, source_code);
             }
 
             return match previous.kind() {
@@ -268,103 +265,102 @@ impl NormalizerFixer {
                 "variable_name" |
                 "encapsed_string" |
                 "binary_expression" |
-                "member_access_expression" => self.space_before(&node, &source_code),
-                _ => self.line_break_before(&node, &source_code),
+                "member_access_expression" => self.space_before(node, source_code),
+                _ => self.line_break_before(node, source_code),
             }
         }
 
-        self.line_break_before(&node, &source_code)
+        self.line_break_before(node, source_code)
     }
 
-    fn handle_static_modifier(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_static_modifier(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(parent) = node.parent() {
             if self.next_is_within(&parent, &["property_element", "union_type", "function"]) {
-                return self.space_after(&node, &source_code);
+                return self.space_after(node, source_code);
             }
         }
 
-        self.pass_through(&node, &source_code)
+        self.pass_through(node, source_code)
     }
 
-    fn handle_function(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_function(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if self.next_is(node, "name") {
-            return self.space_after(&node, &source_code);
+            return self.space_after(node, source_code);
         }
 
-        self.pass_through(&node, &source_code)
+        self.pass_through(node, source_code)
     }
 
-    fn handle_primitive_parameters(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_primitive_parameters(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(parent) = node.parent() {
-            if parent.kind() == "primitive_type" {
-                if parent.next_sibling().is_none() {
+            if parent.kind() == "primitive_type"
+                && parent.next_sibling().is_none() {
                     // If it is at the tail of the function, we do nothing
                     if let Some(parent) = parent.parent() {
                         if self.next_is(&parent, "compound_statement") {
-                            return self.pass_through(&node, &source_code);
+                            return self.pass_through(node, source_code);
                         }
                     }
 
-                    return self.space_after(&node, &source_code);
+                    return self.space_after(node, source_code);
                 }
-            }
         }
 
-        self.pass_through(&node, &source_code)
+        self.pass_through(node, source_code)
     }
 
-    fn handle_dollar_kind(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
-        self.pass_through(&node, &source_code)
+    fn handle_dollar_kind(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
+        self.pass_through(node, source_code)
     }
 
-    fn handle_visibility_modifier(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_visibility_modifier(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(parent) = node.parent() {
             if self.next_is_within(&parent, &["property_element", "readonly_modifier", "union_type", "static_modifier", "function"]) {
-                return self.space_after(&node, &source_code);
+                return self.space_after(node, source_code);
             }
 
             if let Some(previous) = parent.prev_sibling() {
                 if previous.kind() == "as" {
-                    return self.space_after(&node, &source_code);
+                    return self.space_after(node, source_code);
                 }
             }
         }
 
-        self.pass_through(&node, &source_code)
+        self.pass_through(node, source_code)
     }
 
-    fn handle_comment(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_comment(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if node.next_sibling().is_some() {
-            return self.line_break_after(&node, &source_code);
+            return self.line_break_after(node, source_code);
         }
 
-        self.pass_through(&node, &source_code)
+        self.pass_through(node, source_code)
     }
 
-    fn handle_use(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_use(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(parent) = node.parent() {
             if let Some(previous) = parent.prev_sibling() {
                 if previous.kind() == "formal_parameters" {
-                    return self.space_before_and_after(&node, &source_code);
+                    return self.space_before_and_after(node, source_code);
                 }
             }
         }
 
-        self.space_after(&node, &source_code)
+        self.space_after(node, source_code)
     }
 
-    fn handle_name_kind(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_name_kind(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if let Some(parent) = node.parent() {
             if self.parent_is(&parent, "attribute_group") {
                 if self.next_is(&parent, ",") {
-                    return self.pass_through(&node, &source_code);
+                    return self.pass_through(node, source_code);
                 }
 
-                return self.line_break_after(&node, &source_code);
+                return self.line_break_after(node, source_code);
             }
 
             if self.next_is_within(&parent, &["|", "::", "use_list"]) {
-                return self.pass_through(&node, &source_code);
+                return self.pass_through(node, source_code);
             }
 
             if parent.kind() == "qualified_name" {
@@ -375,16 +371,16 @@ impl NormalizerFixer {
 
                 if let Some(parent) = sequence {
                     if self.is_within(&parent, &[";", "|"]) {
-                        return self.pass_through(&node, &source_code);
+                        return self.pass_through(node, source_code);
                     }
                 }
 
 
                 if self.next_is(&parent, ";") {
-                    return self.pass_through(&node, &source_code);
+                    return self.pass_through(node, source_code);
                 }
 
-                return self.space_after(&node, &source_code);
+                return self.space_after(node, source_code);
             }
 
             if parent.kind() == "named_type" {
@@ -396,22 +392,22 @@ impl NormalizerFixer {
 
                 if let Some(parent) = sequence {
                     if parent.kind() == "compound_statement" {
-                        return self.pass_through(&node, &source_code);
+                        return self.pass_through(node, source_code);
                     }
                 }
 
-                return self.space_after(&node, &source_code);
+                return self.space_after(node, source_code);
             }
         }
 
-        self.pass_through(&node, &source_code)
+        self.pass_through(node, source_code)
     }
 
-    fn handle_operators(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn handle_operators(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         if node.kind() == ":" {
             if let Some(previous) = node.prev_sibling() {
                 if ["name", "formal_parameters", "?"].contains(&previous.kind()) {
-                    return self.space_after(&node, &source_code);
+                    return self.space_after(node, source_code);
                 }
             }
         }
@@ -419,7 +415,7 @@ impl NormalizerFixer {
         if ["+", "-"].contains(&node.kind()) {
             if let Some(parent) = node.parent() {
                 if parent.kind() == "unary_op_expression" {
-                    return self.pass_through(&node, &source_code);
+                    return self.pass_through(node, source_code);
                 }
             }
         }
@@ -427,23 +423,39 @@ impl NormalizerFixer {
         if node.kind() == "?" {
             if let Some(next) = node.next_sibling() {
                 if next.kind() == "named_type" {
-                    return self.pass_through(&node, &source_code);
+                    return self.pass_through(node, source_code);
                 }
 
                 if next.kind() == ":" {
-                    return self.space_before(&node, &source_code);
+                    return self.space_before(node, source_code);
                 }
             }
         }
 
-        self.space_before_and_after(&node, &source_code)
+        self.space_before_and_after(node, source_code)
     }
 
-    fn normalize_block(&self, node: &Node, source_code: &Vec<u8>) -> Vec<u8> {
+    fn normalize_block(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         node.children(&mut node.walk())
             .map(|child| {
+                // The content of a string is what the program outputs, it is never reformatted.
+                if is_string_like(&child) {
+                    return self.pass_through(&child, source_code);
+                }
+
+                // Inline HTML within a statement, e.g. in the body of `if (...): ?> ... <?php endif;`
+                if child.kind() == "text_interpolation" {
+                    let mut tokens = self.pass_through(&child, source_code);
+
+                    if let Some(next) = child.next_sibling() {
+                        tokens.extend_from_slice(separator(&source_code[child.end_byte()..next.start_byte()]));
+                    }
+
+                    return tokens;
+                }
+
                 if child.child_count() > 0 {
-                    return self.normalize_block(&child, &source_code);
+                    return self.normalize_block(&child, source_code);
                 }
 
                 // println!("{:?} {:?}", child.kind(), child.utf8_text(&source_code).unwrap());
@@ -456,49 +468,49 @@ impl NormalizerFixer {
                     "." | ".=" |                                                                    // String Operators
                     "?:" | "??" | "?" | ":" |                                                       // Conditional Assignment Operators
                     ">>" | "<<" | "&" | "|" | "^" | ">>=" | "<<=" | "&=" | "|=" | "^="              // Bitwise Operators
-                    => self.handle_operators(&child, &source_code),
+                    => self.handle_operators(&child, source_code),
 
                     // Class related tokens
                     "as" |
                     "=>" |
                     "extends" |
-                    "implements" => self.space_before_and_after(&child, &source_code),
-                    "class" => self.handle_class_kind(&child, &source_code),
-                    "$" => self.handle_dollar_kind(&child, &source_code),
+                    "implements" => self.space_before_and_after(&child, source_code),
+                    "class" => self.handle_class_kind(&child, source_code),
+                    "$" => self.handle_dollar_kind(&child, source_code),
 
                     "null" | "string" | "bool" | "boolean" | "float" | "int" |
                     "array" | "mixed" | "object" | "callable" | "resource"
-                    => self.handle_primitive_parameters(&child, &source_code),
+                    => self.handle_primitive_parameters(&child, source_code),
 
-                    "private" | "public" | "protected" => self.handle_visibility_modifier(&child, &source_code),
+                    "private" | "public" | "protected" => self.handle_visibility_modifier(&child, source_code),
 
                     "readonly" | "final" |
                     "const" | "echo" |
                     "namespace" | "interface" | "trait" |
-                    "new" => self.space_after(&child, &source_code),
-                    "use" => self.handle_use(&child, &source_code),
+                    "new" => self.space_after(&child, source_code),
+                    "use" => self.handle_use(&child, source_code),
 
-                    "comment" => self.handle_comment(&child, &source_code),
+                    "comment" => self.handle_comment(&child, source_code),
 
-                    "#[" => self.line_break_after(&child, &source_code),
-                    "name" => self.handle_name_kind(&child, &source_code),
-                    "return" => self.handle_return(&child, &source_code),
-                    ";" => self.handle_semicolon(&child, &source_code),
-                    "," => self.line_break_after(&child, &source_code),
-                    "function" => self.handle_function(&child, &source_code),
-                    "static" => self.handle_static_modifier(&child, &source_code),
-                    "->" | "?->" => self.line_break_before(&child, &source_code),
+                    "#[" => self.line_break_after(&child, source_code),
+                    "name" => self.handle_name_kind(&child, source_code),
+                    "return" => self.handle_return(&child, source_code),
+                    ";" => self.handle_semicolon(&child, source_code),
+                    "," => self.line_break_after(&child, source_code),
+                    "function" => self.handle_function(&child, source_code),
+                    "static" => self.handle_static_modifier(&child, source_code),
+                    "->" | "?->" => self.line_break_before(&child, source_code),
 
                     // Brackets / Parenthesis
-                    "[" => self.handle_open_array_bracket(&child, &source_code),
-                    "]" => self.handle_close_array_bracket(&child, &source_code),
-                    "{" => self.line_break_before_and_after(&child, &source_code),
-                    "}" => self.handle_close_squiggly_bracket(&child, &source_code),
-                    "(" => self.handle_open_parenthesis(&child, &source_code),
-                    ")" => self.handle_close_parenthesis(&child, &source_code),
+                    "[" => self.handle_open_array_bracket(&child, source_code),
+                    "]" => self.handle_close_array_bracket(&child, source_code),
+                    "{" => self.line_break_before_and_after(&child, source_code),
+                    "}" => self.handle_close_squiggly_bracket(&child, source_code),
+                    "(" => self.handle_open_parenthesis(&child, source_code),
+                    ")" => self.handle_close_parenthesis(&child, source_code),
 
                     // Default
-                    _ => self.pass_through(&child, &source_code)
+                    _ => self.pass_through(&child, source_code)
                 }
             })
             .flat_map(|token| token.to_owned())
@@ -506,16 +518,42 @@ impl NormalizerFixer {
     }
 }
 
-impl Fixer for NormalizerFixer {
-    fn query(&self) -> &str {
-        "(program) @program"
+/// Parts of a template that are kept byte for byte: inline HTML and the tags around each PHP island.
+fn is_verbatim(node: &Node) -> bool {
+    matches!(node.kind(), "text" | "text_interpolation" | "php_tag" | "php_end_tag")
+}
+
+/// Whether the program mixes PHP with inline HTML, or opens with another tag than `<?php`, like `<?=`.
+fn is_template(node: &Node, source_code: &[u8]) -> bool {
+    if node.child(0).filter(|child| &source_code[child.byte_range()] != b"<?php").is_some() {
+        return true;
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
+    node.children(&mut node.walk()).any(|child| matches!(child.kind(), "text" | "text_interpolation"))
+}
+
+/// The whitespace to put between a tag and the PHP code next to it, a line break when there was one already.
+fn separator(gap: &[u8]) -> &'static [u8] {
+    match gap {
+        [] => b"",
+        gap if gap.contains(&b'\n') => LINE_BREAK,
+        _ => b" ",
+    }
+}
+
+impl NormalizerFixer {
+    fn normalize_statement(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
+        match node.kind() {
+            // A comment is a leaf, unlike any statement, so it would be dropped by `normalize_block`.
+            "comment" => self.handle_comment(node, source_code),
+            _ => self.normalize_block(node, source_code),
+        }
+    }
+
+    fn normalize_file(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
         let tokens: Vec<u8> = node
             .children(&mut node.walk())
-            .map(|child| self.normalize_block(&child, &source_code))
-            .flat_map(|token| token.to_owned())
+            .flat_map(|child| self.normalize_statement(&child, source_code))
             .collect();
 
         let mut opening = b"<?php".to_vec();
@@ -529,11 +567,77 @@ impl Fixer for NormalizerFixer {
 
         opening.extend_from_slice(LINE_BREAK);
 
+        opening
+    }
+
+    /// Formats every PHP island of a template on its own, the inline HTML and the tags around the islands are kept
+    /// as they are.
+    fn normalize_template(&self, node: &Node, source_code: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        let mut island = vec![];
+        let mut island_range: Option<(usize, usize)> = None;
+        let mut last_end = node.start_byte();
+
+        for child in node.children(&mut node.walk()) {
+            if !is_verbatim(&child) {
+                island.extend(self.normalize_statement(&child, source_code));
+                island_range = Some((island_range.map_or(child.start_byte(), |(start, _)| start), child.end_byte()));
+
+                continue;
+            }
+
+            match island_range.take() {
+                Some((start, end)) => {
+                    output.extend_from_slice(separator(&source_code[last_end..start]));
+                    output.extend_from_slice(island.trim_ascii());
+                    output.extend_from_slice(separator(&source_code[end..child.start_byte()]));
+
+                    island.clear();
+                }
+                None => output.extend_from_slice(&source_code[last_end..child.start_byte()]),
+            }
+
+            output.extend_from_slice(&source_code[child.byte_range()]);
+            last_end = child.end_byte();
+        }
+
+        match island_range {
+            Some((start, _)) => {
+                output.extend_from_slice(separator(&source_code[last_end..start]));
+                output.extend_from_slice(island.trim_ascii());
+                output.extend_from_slice(LINE_BREAK);
+            }
+            None => output.extend_from_slice(&source_code[last_end..node.end_byte()]),
+        }
+
+        output
+    }
+}
+
+impl Fixer for NormalizerFixer {
+    fn name(&self) -> &'static str {
+        "normalizer"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reformats the whitespace, line breaks and indentation of the whole file."
+    }
+
+    fn query(&self) -> &str {
+        "(program) @program"
+    }
+
+    fn fix(&mut self, node: &Node, source_code: &[u8]) -> Option<Edit> {
+        let inserted_text = match is_template(node, source_code) {
+            true => self.normalize_template(node, source_code),
+            false => self.normalize_file(node, source_code),
+        };
+
         Some(
             Edit {
                 deleted_length: node.end_byte() - node.start_byte(),
                 position: node.start_byte(),
-                inserted_text: opening,
+                inserted_text,
             }
         )
     }
@@ -1484,4 +1588,108 @@ mod tests {
 
         assert_inputs(input, output);
     }
+
+    #[test]
+    fn top_level_comments_are_kept() {
+        let input = indoc! {"
+            <?php
+            // comment 1
+            $variable = 1; // comment 2
+            # comment 3
+        "};
+
+        let output = indoc! {"
+            <?php
+            // comment 1
+            $variable = 1;
+            // comment 2
+            # comment 3
+        "};
+
+        assert_inputs(input, output);
+    }
+
+    #[test]
+    fn phtml_views_keep_inline_html_and_tags() {
+        let input = indoc! {"
+            <!DOCTYPE html>
+            <ul class=\"items\">
+              <?php   foreach ($items as $item):   ?>
+                <li><?=$item?></li>
+              <?php endforeach ?>
+            </ul>
+            <?php
+            $a=1;$b=2;
+            ?>
+            <footer>  <?= $a+$b ?>  </footer>
+        "};
+
+        let output = indoc! {"
+            <!DOCTYPE html>
+            <ul class=\"items\">
+              <?php foreach(
+            $items as $item
+            ) : ?>
+                <li><?=$item?></li>
+              <?php endforeach ?>
+            </ul>
+            <?php
+            $a = 1;
+            $b = 2;
+            ?>
+            <footer>  <?= $a + $b ?>  </footer>
+        "};
+
+        assert_inputs(input, output);
+    }
+
+    #[test]
+    fn closing_tag_and_trailing_html_are_kept() {
+        let input = indoc! {"
+            <?php
+            $a=1;
+            ?>
+            <p>text</p>
+        "};
+
+        let output = indoc! {"
+            <?php
+            $a = 1;
+            ?>
+            <p>text</p>
+        "};
+
+        assert_inputs(input, output);
+    }
+
+    #[test]
+    fn strings_heredocs_and_nowdocs_are_kept_as_is() {
+        let input = indoc! {"
+            <?php
+            $a=  \"{$a->b()} and $a[0]\";
+            $b=<<<EOT
+              Hello {$c->d( 1,2 )}
+                $e[0]
+              EOT;
+            $f=<<<'EOT'
+            raw  ->  text
+            EOT;
+            $g = `ls  -la`;
+        "};
+
+        let output = indoc! {"
+            <?php
+            $a = \"{$a->b()} and $a[0]\";
+            $b = <<<EOT
+              Hello {$c->d( 1,2 )}
+                $e[0]
+              EOT;
+            $f = <<<'EOT'
+            raw  ->  text
+            EOT;
+            $g = `ls  -la`;
+        "};
+
+        assert_inputs(input, output);
+    }
 }
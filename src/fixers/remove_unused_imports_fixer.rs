@@ -6,11 +6,27 @@ use crate::test_utilities::Edit;
 pub struct RemoveUnusedImportsFixer {}
 
 impl Fixer for RemoveUnusedImportsFixer {
+    fn name(&self) -> &'static str {
+        "remove_unused_imports"
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes the imports that are never used."
+    }
+
+    fn runs_before(&self) -> &'static [&'static str] {
+        &["header_line"]
+    }
+
+    fn is_risky(&self) -> bool {
+        true
+    }
+
     fn query(&self) -> &str {
         "(namespace_use_declaration) @use"
     }
 
-    fn fix(&mut self, node: &Node, source_code: &Vec<u8>) -> Option<Edit> {
+    fn fix(&mut self, _node: &Node, _source_code: &[u8]) -> Option<Edit> {
         // Collect all static method calls Class::method()
         todo!();
         // let query = Query::new(node.language(), indoc! {"
@@ -0,0 +1,183 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use tree_sitter::{Parser, Tree};
+
+use crate::atomic::write_atomically;
+use crate::diff::unified_diff;
+use crate::fixer::FixerRunner;
+
+pub struct FormattedFile {
+    pub path: PathBuf,
+    pub original: Vec<u8>,
+    pub formatted: Vec<u8>,
+    pub applied_fixers: Vec<String>,
+    /// Whether the original had syntax errors, only its valid statements were formatted then.
+    pub has_syntax_errors: bool,
+}
+
+impl FormattedFile {
+    pub fn is_changed(&self) -> bool {
+        self.original != self.formatted
+    }
+
+    /// The 1-based line of the first byte that differs between the original and the formatted source.
+    pub fn first_changed_line(&self) -> Option<usize> {
+        let offset = self.original.iter().zip(&self.formatted).position(|(left, right)| left != right)
+            .or_else(|| self.is_changed().then(|| self.original.len().min(self.formatted.len())))?;
+
+        Some(self.original[..offset].iter().filter(|byte| **byte == b'\n').count() + 1)
+    }
+
+    /// Atomically replaces the file on disk with the formatted source, an unchanged file is never touched.
+    pub fn write(&self) -> anyhow::Result<()> {
+        if !self.is_changed() {
+            return Ok(());
+        }
+
+        write_atomically(&self.path, &self.formatted)
+    }
+}
+
+/// What happened to a single file, kept small so the results of a whole project can be held in memory at once.
+pub struct FileResult {
+    pub changed: bool,
+    pub line: Option<usize>,
+    pub applied_fixers: Vec<String>,
+    pub diff: Option<String>,
+    /// The content hash of the file, when what is on disk is known to be formatted.
+    pub hash: Option<u64>,
+}
+
+/// Fails with a diff of what changed when `again`, the result of formatting `file` a second time, is not unchanged.
+pub fn ensure_idempotent(file: &FormattedFile, again: &FormattedFile) -> anyhow::Result<()> {
+    if again.is_changed() {
+        bail!(
+            "Formatting {} is not idempotent, a second pass changes it again:\n{}",
+            file.path.display(),
+            unified_diff(&file.path, &again.original, &again.formatted, false),
+        );
+    }
+
+    Ok(())
+}
+
+/// A runner with every query already compiled and a parser kept from one buffer to the next, so formatting many
+/// files in a row only pays for setting them up once.
+pub struct FormattingSession {
+    runner: FixerRunner,
+    parser: Parser,
+}
+
+impl FormattingSession {
+    pub fn new(mut runner: FixerRunner) -> anyhow::Result<Self> {
+        let parser = FixerRunner::create_parser()?;
+
+        runner.compile_queries(parser.language().context("The parser has no language.")?)?;
+
+        Ok(Self { runner, parser })
+    }
+
+    pub fn runner(&self) -> &FixerRunner {
+        &self.runner
+    }
+
+    /// Parses `source_code` with the parser of the session, reusing the unchanged parts of `old_tree` when it is
+    /// given and was edited to match.
+    pub fn parse(&mut self, source_code: &[u8], old_tree: Option<&Tree>) -> anyhow::Result<Tree> {
+        self.parser.parse(source_code, old_tree).context("Failed to parse source code.")
+    }
+
+    /// Runs the fixer pipeline over an in-memory buffer, `path` is only used to identify it.
+    ///
+    /// When a byte `range` is given, only the syntax nodes covering it are formatted and only the bytes inside it change.
+    pub fn format(&mut self, path: &Path, original: Vec<u8>, range: Option<Range<usize>>) -> anyhow::Result<FormattedFile> {
+        let tree = self.parse(&original, None)?;
+
+        self.format_parsed(path, original, tree, range)
+    }
+
+    /// Same as `format` for a buffer already parsed into `tree`, e.g. one kept up to date as it is being edited.
+    pub fn format_parsed(&mut self, path: &Path, original: Vec<u8>, tree: Tree, range: Option<Range<usize>>) -> anyhow::Result<FormattedFile> {
+        let mut formatted = original.clone();
+        let valid = !tree.root_node().has_error();
+
+        let tree = match range {
+            Some(range) => self.runner.execute_parsed_range(&mut self.parser, tree, &mut formatted, range)?,
+            None => self.runner.execute_parsed(&mut self.parser, tree, &mut formatted)?,
+        };
+
+        // Never hand back a buffer the parser could not make sense of, it would replace the user's code with garbage.
+        if valid && tree.root_node().has_error() {
+            bail!("Formatting {} produced invalid code", path.display());
+        }
+
+        Ok(FormattedFile {
+            path: path.to_owned(),
+            original,
+            formatted,
+            applied_fixers: self.runner.applied_fixers().to_vec(),
+            has_syntax_errors: !valid,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    use crate::fixer::default_runner;
+    use crate::formatter::{ensure_idempotent, FormattedFile, FormattingSession};
+
+    fn file(original: &str, formatted: &str) -> FormattedFile {
+        FormattedFile {
+            path: PathBuf::from("a.php"),
+            original: original.as_bytes().to_vec(),
+            formatted: formatted.as_bytes().to_vec(),
+            applied_fixers: vec!["normalizer".to_owned()],
+            has_syntax_errors: false,
+        }
+    }
+
+    #[test]
+    fn it_fails_with_a_diff_when_a_second_pass_changes_the_code() {
+        let first = file("<?php\n$a=1;\n", "<?php\n$a = 1;\n");
+
+        assert!(ensure_idempotent(&first, &file("<?php\n$a = 1;\n", "<?php\n$a = 1;\n")).is_ok());
+
+        let error = ensure_idempotent(&first, &file("<?php\n$a = 1;\n", "<?php\n$a  =  1;\n")).unwrap_err().to_string();
+
+        assert!(error.starts_with("Formatting a.php is not idempotent"));
+        assert!(error.contains("-$a = 1;\n+$a  =  1;\n"));
+    }
+
+    /// Formats the same buffer `times` times, through a single session or a new one every time, as the language server
+    /// used to do for every open document.
+    fn time_formatting(times: usize, reuse_session: bool) -> Duration {
+        let start = Instant::now();
+        let mut session = FormattingSession::new(default_runner()).unwrap();
+
+        for _ in 0..times {
+            if !reuse_session {
+                session = FormattingSession::new(default_runner()).unwrap();
+            }
+
+            session.format(Path::new("a.php"), b"<?php\nif ($a) {\nfoo( $b,$c );\n}\n".to_vec(), None).unwrap();
+        }
+
+        start.elapsed()
+    }
+
+    #[test]
+    #[ignore = "timing comparison, run it with --ignored --nocapture"]
+    fn it_is_faster_to_reuse_a_session() {
+        let reused = time_formatting(200, true);
+        let created = time_formatting(200, false);
+
+        println!("200 buffers formatted in {:?} with a single session, in {:?} with a new session for each", reused, created);
+
+        assert!(reused < created);
+    }
+}
@@ -0,0 +1,125 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context};
+
+use crate::discovery::is_php_file;
+
+fn git(args: &[&str], directory: &Path) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(directory)
+        .output()
+        .context("Failed to run git, is it installed?")?;
+
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn toplevel() -> anyhow::Result<PathBuf> {
+    let output = git(&["rev-parse", "--show-toplevel"], Path::new("."))?;
+
+    Ok(PathBuf::from(output.trim_end()))
+}
+
+/// Lists the PHP files below `paths` that were modified or added since `revision`, as well as untracked ones.
+///
+/// Deleted files are left out, paths are returned sorted and relative to the repository root joined onto it.
+pub fn changed_files(revision: &str, paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let root = toplevel()?;
+
+    let changed = git(&["diff", "--name-only", "-z", "--diff-filter=ACMR", revision, "--"], &root)?;
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "-z"], &root)?;
+
+    let scopes = paths
+        .iter()
+        .map(|path| path.canonicalize().with_context(|| format!("No such file or directory: {}", path.display())))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut files: Vec<PathBuf> = changed
+        .split('\0')
+        .chain(untracked.split('\0'))
+        .filter(|file| !file.is_empty())
+        .map(|file| root.join(file))
+        .filter(|file| is_php_file(file))
+        .filter(|file| scopes.iter().any(|scope| file.starts_with(scope)))
+        .collect();
+
+    files.sort();
+    files.dedup();
+
+    Ok(files)
+}
+
+/// Returns the 0-based line ranges of `path` that were added or modified since `revision`.
+///
+/// Files unknown to git at `revision` are reported as changed entirely.
+#[allow(clippy::single_range_in_vec_init)]
+pub fn changed_lines(revision: &str, path: &Path) -> anyhow::Result<Vec<Range<usize>>> {
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().context("Not a file")?.to_string_lossy();
+
+    let tracked = git(&["ls-files", "--error-unmatch", "--", &name], directory).is_ok();
+
+    if !tracked {
+        return Ok(vec![0..usize::MAX]);
+    }
+
+    let diff = git(&["diff", "-U0", "--no-color", revision, "--", &name], directory)?;
+
+    Ok(parse_hunk_ranges(&diff))
+}
+
+/// Extracts the new side of every `@@ -a,b +c,d @@` hunk header.
+fn parse_hunk_ranges(diff: &str) -> Vec<Range<usize>> {
+    diff.lines()
+        .filter_map(|line| line.strip_prefix("@@ "))
+        .filter_map(|line| line.split(' ').find(|part| part.starts_with('+')))
+        .filter_map(|range| {
+            let range = range.trim_start_matches('+');
+
+            let (start, length) = match range.split_once(',') {
+                Some((start, length)) => (start.parse::<usize>().ok()?, length.parse::<usize>().ok()?),
+                None => (range.parse::<usize>().ok()?, 1),
+            };
+
+            // Pure deletions have no new lines, the surrounding line is what changed.
+            let start = start.saturating_sub(1);
+
+            Some(start..start + length.max(1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::git::parse_hunk_ranges;
+
+    #[test]
+    fn it_parses_the_new_side_of_hunk_headers() {
+        let diff = indoc! {"
+        diff --git a/src/Example.php b/src/Example.php
+        index 1111111..2222222 100644
+        --- a/src/Example.php
+        +++ b/src/Example.php
+        @@ -2 +2 @@
+        -$a=1;
+        +$a=2;
+        @@ -10,0 +11,3 @@ function example()
+        +$b=1;
+        +$c=1;
+        +$d=1;
+        @@ -20,2 +23,0 @@
+        -$e=1;
+        -$f=1;
+        "};
+
+        assert_eq!(parse_hunk_ranges(diff), vec![1..2, 10..13, 22..23]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+use tree_sitter::Point;
+
+use crate::test_utilities::Edit;
+
+/// The byte offset at which every line of a source starts, to convert between offsets and positions in
+/// `O(log n)` instead of scanning the source from its start every time.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source_code: &[u8]) -> Self {
+        Self { line_starts: line_starts(source_code, 0).collect() }
+    }
+
+    /// The 0-based line containing `offset`.
+    fn row(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    /// The row and the byte column of `offset`, as tree-sitter expects them.
+    pub fn point(&self, offset: usize) -> Point {
+        let row = self.row(offset);
+
+        Point { row, column: offset - self.line_starts[row] }
+    }
+
+    pub fn offset(&self, point: Point) -> usize {
+        self.line_starts.get(point.row).map_or(usize::MAX, |start| start + point.column)
+    }
+
+    /// The bytes of the line containing `offset`, without its line break.
+    pub fn line(&self, source_code: &[u8], offset: usize) -> Range<usize> {
+        let row = self.row(offset);
+        let end = self.line_starts.get(row + 1).map_or(source_code.len(), |start| start - 1);
+
+        self.line_starts[row]..end
+    }
+
+    /// The 0-based column of `offset` counted in characters rather than bytes, only its own line is looked at.
+    pub fn character_column(&self, source_code: &[u8], offset: usize) -> usize {
+        let start = self.line_starts[self.row(offset)];
+
+        String::from_utf8_lossy(&source_code[start..offset]).chars().count()
+    }
+
+    /// The 0-based column of `offset` counted in UTF-16 code units, as language server clients count them.
+    pub fn utf16_column(&self, source_code: &str, offset: usize) -> usize {
+        let start = self.line_starts[self.row(offset)];
+
+        source_code[start..offset].encode_utf16().count()
+    }
+
+    /// The offset of the UTF-16 `column` of `row`, clamped to the end of the line, or of the source past its last line.
+    pub fn utf16_offset(&self, source_code: &str, row: usize, column: usize) -> usize {
+        let start = self.offset(Point { row, column: 0 }).min(source_code.len());
+        let line = source_code[start..].split('\n').next().unwrap_or_default();
+        let mut units = 0;
+
+        for (index, character) in line.char_indices() {
+            if units >= column {
+                return start + index;
+            }
+
+            units += character.len_utf16();
+        }
+
+        start + line.len()
+    }
+
+    /// Updates the index for `edit`, which is about to be or has just been applied to the source.
+    pub fn apply(&mut self, edit: &Edit) {
+        let old_end = edit.position + edit.deleted_length;
+        let new_end = edit.position + edit.inserted_text.len();
+
+        // The lines starting within the deleted text are gone, the ones of the inserted text are new.
+        let first = self.line_starts.partition_point(|start| *start <= edit.position);
+        let last = self.line_starts.partition_point(|start| *start <= old_end);
+        let inserted: Vec<usize> = line_starts(&edit.inserted_text, edit.position).skip(1).collect();
+        let shifted = first + inserted.len();
+
+        self.line_starts.splice(first..last, inserted);
+
+        for start in &mut self.line_starts[shifted..] {
+            *start = *start - old_end + new_end;
+        }
+    }
+}
+
+/// The offsets at which the lines of `text` start, when `text` itself starts at `offset`.
+fn line_starts(text: &[u8], offset: usize) -> impl Iterator<Item = usize> + '_ {
+    std::iter::once(offset).chain(text.iter().enumerate().filter(|(_, byte)| **byte == b'\n').map(move |(index, _)| offset + index + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use crate::line_index::LineIndex;
+    use crate::test_utilities::Edit;
+
+    #[test]
+    fn it_converts_offsets_and_points() {
+        let source_code = "<?php\n$é = 'à';\n\n$b = 1;".as_bytes();
+        let index = LineIndex::new(source_code);
+
+        assert_eq!(index.point(0), Point { row: 0, column: 0 });
+        assert_eq!(index.point(15), Point { row: 1, column: 9 });
+        assert_eq!(index.point(19), Point { row: 3, column: 0 });
+        assert_eq!(index.offset(Point { row: 1, column: 9 }), 15);
+        assert_eq!(index.character_column(source_code, 15), 7);
+        assert_eq!(index.line(source_code, 15), 6..17);
+        assert_eq!(index.line(source_code, 19), 19..26);
+    }
+
+    #[test]
+    fn it_converts_utf16_columns() {
+        let source_code = "<?php\n$é = '😀';\n";
+        let index = LineIndex::new(source_code.as_bytes());
+
+        assert_eq!(index.utf16_column(source_code, 17), 8);
+        assert_eq!(index.utf16_offset(source_code, 1, 8), 17);
+        assert_eq!(index.utf16_offset(source_code, 1, 99), 19);
+        assert_eq!(index.utf16_offset(source_code, 5, 0), 20);
+    }
+
+    #[test]
+    fn it_stays_up_to_date_as_edits_are_applied() {
+        let mut source_code = b"<?php\n$a=1;\n$b=2;\n$c=3;\n".to_vec();
+        let mut index = LineIndex::new(&source_code);
+
+        let edits = [
+            Edit { position: 8, deleted_length: 7, inserted_text: b" = 1;\n\n$b".to_vec() },
+            Edit { position: 0, deleted_length: 6, inserted_text: b"<?php ".to_vec() },
+            Edit { position: 20, deleted_length: 0, inserted_text: b"\n// x\n".to_vec() },
+        ];
+
+        for edit in edits {
+            source_code.splice(edit.position..edit.position + edit.deleted_length, edit.inserted_text.iter().copied());
+            index.apply(&edit);
+
+            assert_eq!(index.line_starts, LineIndex::new(&source_code).line_starts);
+        }
+    }
+}
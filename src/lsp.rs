@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _};
+use lsp_types::request::{Formatting, OnTypeFormatting, RangeFormatting, Request as _};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentFormattingParams,
+    DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams, OneOf, Position,
+    Range, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+use serde::de::DeserializeOwned;
+use similar::{DiffTag, TextDiff};
+use tree_sitter::{InputEdit, Tree};
+
+use crate::fixer::FixerRunner;
+use crate::formatter::FormattingSession;
+use crate::line_index::LineIndex;
+use crate::test_utilities::Edit;
+
+/// An open document and its syntax tree, kept up to date with every change so formatting it never starts from
+/// scratch.
+struct Document {
+    text: String,
+    index: LineIndex,
+    tree: Tree,
+}
+
+impl Document {
+    fn new(session: &mut FormattingSession, text: String) -> anyhow::Result<Self> {
+        let tree = session.parse(text.as_bytes(), None)?;
+
+        Ok(Self { index: LineIndex::new(text.as_bytes()), text, tree })
+    }
+
+    /// Applies an incremental change, the previous tree is edited so the re-parse only redoes what changed.
+    fn change(&mut self, session: &mut FormattingSession, range: Option<Range>, text: &str) -> anyhow::Result<()> {
+        let Some(range) = range else {
+            *self = Self::new(session, text.to_owned())?;
+
+            return Ok(());
+        };
+
+        let start_byte = self.offset_at(&range.start);
+        let old_end_byte = self.offset_at(&range.end).max(start_byte);
+        let start_position = self.index.point(start_byte);
+        let old_end_position = self.index.point(old_end_byte);
+
+        self.text.replace_range(start_byte..old_end_byte, text);
+        self.index.apply(&Edit { position: start_byte, deleted_length: old_end_byte - start_byte, inserted_text: text.as_bytes().to_vec() });
+
+        let new_end_byte = start_byte + text.len();
+
+        self.tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position: self.index.point(new_end_byte),
+        });
+
+        self.tree = session.parse(self.text.as_bytes(), Some(&self.tree))?;
+
+        Ok(())
+    }
+
+    /// Formats the document, or only the code covering `range`, and returns the edits turning it into the result.
+    fn format(&self, session: &mut FormattingSession, uri: &Url, range: Option<Range>) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        // Nothing sensible can come out of a broken tree, leave the buffer alone until it parses again.
+        if self.tree.root_node().has_error() {
+            return Ok(None);
+        }
+
+        let range = range.map(|range| self.offset_at(&range.start)..self.offset_at(&range.end));
+        let file = session.format_parsed(Path::new(uri.path()), self.text.as_bytes().to_vec(), self.tree.clone(), range)?;
+        let formatted = String::from_utf8(file.formatted).context("The formatted code is not valid UTF-8.")?;
+
+        Ok(Some(text_edits(&self.index, &self.text, &formatted)))
+    }
+
+    /// Converts an LSP position, counted in UTF-16 code units, into a byte offset.
+    fn offset_at(&self, position: &Position) -> usize {
+        self.index.utf16_offset(&self.text, position.line as usize, position.character as usize)
+    }
+}
+
+/// Converts a byte offset into an LSP position, counted in UTF-16 code units.
+fn position_at(index: &LineIndex, text: &str, offset: usize) -> Position {
+    Position::new(index.point(offset).row as u32, index.utf16_column(text, offset) as u32)
+}
+
+/// The smallest set of whole-line edits turning `original`, indexed by `index`, into `formatted`.
+fn text_edits(index: &LineIndex, original: &str, formatted: &str) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(original, formatted);
+    let new_lines = diff.new_slices();
+
+    let mut line_offsets = vec![0];
+
+    for line in diff.old_slices() {
+        line_offsets.push(line_offsets.last().unwrap() + line.len());
+    }
+
+    diff.ops()
+        .iter()
+        .map(|op| op.as_tag_tuple())
+        .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+        .map(|(_, old_range, new_range)| TextEdit {
+            range: Range::new(
+                position_at(index, original, line_offsets[old_range.start]),
+                position_at(index, original, line_offsets[old_range.end]),
+            ),
+            new_text: new_lines[new_range].concat(),
+        })
+        .collect()
+}
+
+fn params<T: DeserializeOwned>(request: &Request) -> anyhow::Result<T> {
+    serde_json::from_value(request.params.clone()).with_context(|| format!("Invalid params for {}", request.method))
+}
+
+/// Every open document, formatted with a single session so the fixer queries are only compiled once.
+struct Server {
+    documents: HashMap<Url, Document>,
+    session: FormattingSession,
+}
+
+fn document<'a>(documents: &'a mut HashMap<Url, Document>, uri: &Url) -> anyhow::Result<&'a mut Document> {
+    documents.get_mut(uri).with_context(|| format!("Unknown document {}", uri))
+}
+
+impl Server {
+    fn new(runner: FixerRunner) -> anyhow::Result<Self> {
+        Ok(Self { documents: HashMap::new(), session: FormattingSession::new(runner)? })
+    }
+
+    fn format(&mut self, uri: &Url, range: Option<Range>) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        document(&mut self.documents, uri)?.format(&mut self.session, uri, range)
+    }
+
+    fn handle_request(&mut self, request: &Request) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        match request.method.as_str() {
+            Formatting::METHOD => {
+                let params: DocumentFormattingParams = params(request)?;
+
+                self.format(&params.text_document.uri, None)
+            }
+            RangeFormatting::METHOD => {
+                let params: DocumentRangeFormattingParams = params(request)?;
+
+                self.format(&params.text_document.uri, Some(params.range))
+            }
+            OnTypeFormatting::METHOD => {
+                let params: DocumentOnTypeFormattingParams = params(request)?;
+                let position = params.text_document_position.position;
+                let line = Range::new(Position::new(position.line, 0), Position::new(position.line + 1, 0));
+
+                self.format(&params.text_document_position.text_document.uri, Some(line))
+            }
+            method => bail!("Unhandled method {}", method),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> anyhow::Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+
+                let document = Document::new(&mut self.session, params.text_document.text)?;
+
+                self.documents.insert(params.text_document.uri, document);
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+                let document = document(&mut self.documents, &params.text_document.uri)?;
+
+                for change in params.content_changes {
+                    document.change(&mut self.session, change.range, &change.text)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+
+                self.documents.remove(&params.text_document.uri);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "}".to_owned(),
+            more_trigger_character: Some(vec![";".to_owned()]),
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// Serves `connection` until the client asks for a shutdown.
+pub fn serve(connection: &Connection, runner: FixerRunner) -> anyhow::Result<()> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut server = Server::new(runner)?;
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+
+                let response = match server.handle_request(&request) {
+                    Ok(edits) => Response::new_ok(request.id, edits),
+                    Err(error) => Response::new_err(request.id, ErrorCode::InternalError as i32, format!("{:#}", error)),
+                };
+
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Err(error) = server.handle_notification(notification) {
+                    eprintln!("Error: {:#}", error);
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the language server over stdin and stdout.
+pub fn run(runner: FixerRunner) -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    serve(&connection, runner)?;
+
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range, TextEdit};
+
+    use crate::fixer::default_runner;
+    use crate::line_index::LineIndex;
+    use crate::lsp::{Document, Server, text_edits};
+
+    #[test]
+    fn it_converts_utf16_positions() {
+        let mut server = Server::new(default_runner()).unwrap();
+        let document = Document::new(&mut server.session, "<?php\n$é = '😀';\n".to_owned()).unwrap();
+
+        assert_eq!(document.offset_at(&Position::new(1, 8)), 17);
+        assert_eq!(document.offset_at(&Position::new(1, 99)), 19);
+    }
+
+    #[test]
+    fn it_only_edits_the_lines_that_changed() {
+        let original = "<?php\n$a=1;\n$b = 2;";
+        let edits = text_edits(&LineIndex::new(original.as_bytes()), original, "<?php\n$a = 1;\n$b = 2;");
+
+        assert_eq!(edits, vec![TextEdit {
+            range: Range::new(Position::new(1, 0), Position::new(2, 0)),
+            new_text: "$a = 1;\n".to_owned(),
+        }]);
+    }
+
+    #[test]
+    fn it_keeps_the_tree_in_sync_with_incremental_changes() {
+        let mut server = Server::new(default_runner()).unwrap();
+        let mut document = Document::new(&mut server.session, "<?php\n$a = 1;\n".to_owned()).unwrap();
+
+        document.change(&mut server.session, Some(Range::new(Position::new(1, 5), Position::new(1, 6))), "2").unwrap();
+        document.change(&mut server.session, Some(Range::new(Position::new(2, 0), Position::new(2, 0))), "$b=3;\n").unwrap();
+
+        assert_eq!(document.text, "<?php\n$a = 2;\n$b=3;\n");
+        assert_eq!(document.index.point(document.text.len()).row, 3);
+        assert_eq!(document.format(&mut server.session, &"file:///a.php".parse().unwrap(), None).unwrap().unwrap(), vec![TextEdit {
+            range: Range::new(Position::new(2, 0), Position::new(3, 0)),
+            new_text: "$b = 3;\n".to_owned(),
+        }]);
+    }
+}
@@ -1,44 +1,389 @@
 #![allow(dead_code)]
-#![allow(warnings)]
 
+use std::cmp::Reverse;
 use std::fs;
+use std::io::{IsTerminal, Read, stdin, stdout, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitCode;
 
-use crate::fixer::{Fixer, FixerRunner};
-use crate::fixers::array_bracket_space_fixer::ArrayBracketSpaceFixer;
-use crate::fixers::declare_directive_existence_fixer::DeclareDirectiveExistenceFixer;
-use crate::fixers::declare_directive_space_fixer::DeclareDirectiveSpaceFixer;
-use crate::fixers::function_arguments_space_fixer::FunctionArgumentsSpaceFixer;
-use crate::fixers::header_line_fixer::HeaderLineFixer;
-use crate::fixers::indent_bracket_body_fixer::IndentBracketBodyFixer;
-use crate::fixers::indent_chained_call_fixer::IndentChainedCallFixer;
-use crate::fixers::normalizer_fixer::NormalizerFixer;
+use anyhow::{anyhow, bail, Context};
 
+use clap::Parser;
+
+use crate::cache::{Cache, content_hash};
+use crate::cli::{Cli, Command, EXIT_CHANGES_NEEDED, EXIT_ERROR, EXIT_SUCCESS};
+use crate::diff::unified_diff;
+use crate::discovery::Discovery;
+use crate::fixer::{DEFAULT_MAX_ITERATIONS, FixerRunner};
+use crate::formatter::{ensure_idempotent, FileResult, FormattedFile, FormattingSession};
+use crate::range::line_range_to_bytes;
+use crate::reporter::FileReport;
+use crate::reporters::create_reporter;
+
+mod atomic;
+mod cache;
+mod cli;
+mod daemon;
+mod diff;
+mod directives;
+mod discovery;
+mod equivalence;
 mod fixers;
+mod formatter;
+mod git;
+mod line_index;
+mod lsp;
+mod pool;
+mod range;
+mod reporter;
+mod reporters;
+mod strings;
+mod syntax;
 mod test_utilities;
+mod watch;
 mod constants;
 mod fixer;
 
-fn main() -> anyhow::Result<()> {
+/// Formats the source code given on stdin, nothing is written to stdout unless formatting succeeded entirely,
+/// so editors never replace their buffer with a half formatted one.
+fn run_stdin(cli: &Cli, discovery: &Discovery, session: &mut FormattingSession) -> anyhow::Result<u8> {
+    let path = cli.stdin_filepath.as_deref().unwrap_or(Path::new("<stdin>"));
+    let mut source_code = vec![];
+
+    stdin().read_to_end(&mut source_code).context("Failed to read from stdin")?;
+
+    // An excluded file is echoed back untouched, the editor still expects its buffer on stdout.
+    if cli.force_exclude && cli.stdin_filepath.is_some() && discovery.is_excluded(path) {
+        if !cli.is_dry_run() {
+            stdout().write_all(&source_code).context("Failed to write to stdout")?;
+        }
+
+        return Ok(EXIT_SUCCESS);
+    }
+
+    let range = cli.byte_range(&source_code);
+    let file = format(cli, session, path, source_code, range).with_context(|| format!("Failed to format {}", path.display()))?;
+
+    if cli.diff && file.is_changed() {
+        print!("{}", unified_diff(path, &file.original, &file.formatted, stdout().is_terminal()));
+    }
+
+    if !cli.is_dry_run() {
+        stdout().write_all(&file.formatted).context("Failed to write to stdout")?;
+    }
+
+    if cli.is_dry_run() && file.is_changed() {
+        return Ok(EXIT_CHANGES_NEEDED);
+    }
+
+    Ok(EXIT_SUCCESS)
+}
+
+/// Formats through the daemon when one is running, or in this process otherwise.
+fn format_once(cli: &Cli, session: &mut FormattingSession, path: &Path, original: Vec<u8>, range: Option<Range<usize>>) -> anyhow::Result<FormattedFile> {
+    // The daemon always refuses syntax errors and uses the default iteration limit, so it can't be used otherwise.
+    if cli.daemon && !cli.allow_syntax_errors && cli.max_iterations == DEFAULT_MAX_ITERATIONS {
+        if let Some(file) = daemon::forward(&cli.socket, path, &original, range.clone())? {
+            return Ok(file);
+        }
+    }
+
+    session.format(path, original, range)
+}
+
+/// Formats the source code, a second time with `--verify-idempotent` to make sure the result is stable.
+fn format(cli: &Cli, session: &mut FormattingSession, path: &Path, original: Vec<u8>, range: Option<Range<usize>>) -> anyhow::Result<FormattedFile> {
+    let file = format_once(cli, session, path, original, range.clone())?;
+
+    // The second pass always runs in this process, the daemon remembers its own result as formatted and would send
+    // it back as is.
+    if cli.verify_idempotent {
+        // Only the bytes inside of the range changed, so the same code now ends that much further or sooner.
+        let range = range.map(|range| range.start..(range.end + file.formatted.len()).saturating_sub(file.original.len()));
+
+        ensure_idempotent(&file, &session.format(path, file.formatted.clone(), range)?)?;
+    }
+
+    Ok(file)
+}
+
+/// Formats only the code covering `lines` (0-based, in the original), one range after the other from the last to the
+/// first, so formatting one never moves the lines of those still to come.
+fn format_lines(cli: &Cli, session: &mut FormattingSession, path: &Path, original: Vec<u8>, lines: &[Range<usize>]) -> anyhow::Result<FormattedFile> {
+    let mut lines = lines.to_vec();
+    let mut file = FormattedFile {
+        path: path.to_owned(),
+        original: original.clone(),
+        formatted: original,
+        applied_fixers: vec![],
+        has_syntax_errors: false,
+    };
+
+    lines.sort_by_key(|range| Reverse(range.start));
+
+    for range in lines.iter().filter(|range| !range.is_empty()) {
+        let bytes = line_range_to_bytes(&file.formatted, &(range.start + 1..=range.end));
+        let part = format(cli, session, path, file.formatted.clone(), Some(bytes))?;
+
+        for fixer in part.applied_fixers {
+            if !file.applied_fixers.contains(&fixer) {
+                file.applied_fixers.push(fixer);
+            }
+        }
+
+        file.formatted = part.formatted;
+        file.has_syntax_errors |= part.has_syntax_errors;
+    }
+
+    Ok(file)
+}
+
+fn create_runner(cli: &Cli) -> FixerRunner {
     let mut runner = FixerRunner::new();
 
-    let fixers: [fn() -> Box<dyn Fixer>; 1] = [
-        || Box::new(NormalizerFixer {}),
-        // || Box::new(ArrayBracketSpaceFixer {}),
-        // || Box::new(DeclareDirectiveSpaceFixer {}),
-        // || Box::new(DeclareDirectiveExistenceFixer {}),
-        // || Box::new(FunctionArgumentsSpaceFixer {}),
-        // || Box::new(IndentBracketBodyFixer {}),
-        //|| Box::new(IndentChainedCallFixer {}),
-        // || Box::new(HeaderLineFixer {}),
-    ];
+    fixers::default_fixers().into_iter().for_each(|fixer| runner.add_fixer(fixer));
+    runner.allow_syntax_errors(cli.allow_syntax_errors);
+    runner.max_iterations(cli.max_iterations);
+
+    runner
+}
+
+fn create_session(cli: &Cli) -> anyhow::Result<FormattingSession> {
+    FormattingSession::new(create_runner(cli))
+}
+
+fn process_file(cli: &Cli, cache: Option<&Cache>, session: &mut FormattingSession, path: &Path, colored: bool) -> anyhow::Result<FileResult> {
+    let original = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let hash = content_hash(&original);
+
+    if cache.filter(|cache| cache.is_formatted(path, hash)).is_some() {
+        return Ok(FileResult { changed: false, line: None, applied_fixers: vec![], diff: None, hash: Some(hash) });
+    }
+
+    let lines = match &cli.changed_since {
+        Some(revision) if cli.changed_lines_only => Some(git::changed_lines(revision, path)?),
+        _ => None,
+    };
+
+    let range = cli.byte_range(&original);
+    let file = match &lines {
+        Some(lines) => format_lines(cli, session, path, original, lines)?,
+        None => format(cli, session, path, original, range)?,
+    };
+
+    if file.is_changed() && !cli.is_dry_run() {
+        file.write()?;
+    }
+
+    Ok(FileResult {
+        changed: file.is_changed(),
+        line: file.first_changed_line(),
+        applied_fixers: file.applied_fixers.clone(),
+        diff: (cli.diff && file.is_changed()).then(|| unified_diff(&file.path, &file.original, &file.formatted, colored)),
+        // A file formatted only partially can't be trusted as formatted on the next full run, neither can one with
+        // syntax errors, the next run has to report them again.
+        hash: match (file.is_changed(), cli.is_dry_run(), lines.is_some() || cli.is_range() || file.has_syntax_errors) {
+            (_, _, true) => None,
+            (false, _, _) => Some(hash),
+            (true, false, _) => Some(content_hash(&file.formatted)),
+            (true, true, _) => None,
+        },
+    })
+}
+
+fn list_fixers(session: &FormattingSession) {
+    let width = session.runner().fixers().map(|fixer| fixer.name().len()).max().unwrap_or_default();
+
+    for fixer in session.runner().fixers() {
+        let risky = if fixer.is_risky() { " Risky, it may change what the code does." } else { "" };
+
+        println!("{:width$}  {}{}", fixer.name(), fixer.description(), risky, width = width);
+    }
+}
+
+fn run(cli: &Cli) -> anyhow::Result<u8> {
+    match &cli.command {
+        Some(Command::Lsp) => {
+            lsp::run(create_runner(cli))?;
+
+            return Ok(EXIT_SUCCESS);
+        }
+        Some(Command::Daemon { socket }) => {
+            daemon::run(socket, pool::default_jobs(), || create_runner(cli))?;
+
+            return Ok(EXIT_SUCCESS);
+        }
+        None => {}
+    }
+
+    if cli.list_fixers {
+        list_fixers(&create_session(cli)?);
+
+        return Ok(EXIT_SUCCESS);
+    }
+
+    let discovery = Discovery::new(&cli.exclude, cli.force_exclude)?;
+
+    if cli.is_stdin() {
+        return run_stdin(cli, &discovery, &mut create_session(cli)?);
+    }
+
+    if let Some(directory) = &cli.watch {
+        watch::watch(directory, &discovery, &mut create_session(cli)?)?;
+
+        return Ok(EXIT_SUCCESS);
+    }
+
+    let files = match &cli.changed_since {
+        Some(revision) => git::changed_files(revision, &cli.paths)?
+            .into_iter()
+            .filter(|path| !discovery.is_excluded(path))
+            .collect(),
+        None => discovery.discover(&cli.paths)?,
+    };
+
+    if cli.is_range() && files.len() != 1 {
+        bail!("--lines and --byte-range can only be used with a single file, {} were given.", files.len());
+    }
+
+    let colored = stdout().is_terminal();
+    let jobs = cli.jobs.unwrap_or_else(pool::default_jobs);
+    // Set up once here so a broken query is reported right away, every worker then sets up its own session.
+    let session = create_session(cli)?;
+    let mut cache = match cli.no_cache {
+        true => None,
+        false => Some(Cache::load(&cli.cache_file, &session.runner().fixer_names(), &session.runner().options())),
+    };
+
+    let results = pool::run_parallel(&files, jobs, || create_session(cli), |session, path| {
+        let session = session.as_mut().map_err(|error| anyhow!("{:#}", error))?;
+
+        process_file(cli, cache.as_ref(), session, path, colored)
+    });
+
+    if let Some(cache) = cache.as_mut() {
+        for (path, result) in files.iter().zip(&results) {
+            match result {
+                Ok(FileResult { hash: Some(hash), .. }) => cache.insert(path.to_owned(), *hash),
+                _ => cache.remove(path),
+            }
+        }
+
+        if let Err(error) = cache.save(&cli.cache_file) {
+            eprintln!("Warning: {:#}", error);
+        }
+    }
+
+    // Everything is reported only once all workers are done, in the order the files were discovered.
+    let mut reports = vec![];
+
+    for (path, result) in files.iter().zip(results) {
+        let report = match result {
+            Ok(result) => {
+                if let Some(diff) = &result.diff {
+                    print!("{}", diff);
+                }
+
+                FileReport {
+                    path: path.to_owned(),
+                    changed: result.changed,
+                    line: result.line,
+                    applied_fixers: result.applied_fixers,
+                    error: None,
+                }
+            }
+            Err(error) => {
+                eprintln!("Failed to format {}: {:#}", path.display(), error);
+
+                FileReport {
+                    path: path.to_owned(),
+                    changed: false,
+                    line: None,
+                    applied_fixers: vec![],
+                    error: Some(format!("{:#}", error)),
+                }
+            }
+        };
+
+        reports.push(report);
+    }
+
+    let changed = reports.iter().filter(|report| report.changed).count();
+    let failed = reports.iter().filter(|report| report.error.is_some()).count();
+
+    if cli.diff {
+        // The diff has to stay a valid patch, so the summary goes to stderr instead.
+        eprintln!("{} of {} files would be reformatted.", changed, files.len());
+    } else if cli.check {
+        create_reporter(cli.format).report(&reports, &mut stdout()).context("Failed to write the report")?;
+    } else {
+        for report in reports.iter().filter(|report| report.changed) {
+            println!("Formatted {}", report.path.display());
+        }
+
+        println!("{} of {} files formatted.", changed, files.len());
+    }
+
+    if failed > 0 {
+        eprintln!("{} files could not be formatted.", failed);
+
+        return Ok(EXIT_ERROR);
+    }
+
+    if cli.is_dry_run() && changed > 0 {
+        return Ok(EXIT_CHANGES_NEEDED);
+    }
+
+    Ok(EXIT_SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::Parser;
+    use indoc::indoc;
 
-    fixers.iter().for_each(|fixer| runner.add_fixer(fixer()));
+    use crate::cli::Cli;
+    use crate::{create_session, format_lines};
 
-    let mut source_code = fs::read_to_string("src/Sample.php")?.as_bytes().to_vec();
+    #[test]
+    fn it_only_formats_the_code_on_the_given_lines() {
+        let cli = Cli::parse_from(["php-code-formatter", "--verify-idempotent"]);
+        let mut session = create_session(&cli).unwrap();
 
-    let tree = runner.execute(&mut source_code)?;
+        let original = indoc! {"
+        <?php
+        function f( $x ){
+        return $x+1;
+        }
+        $a=1;
+        $b=2;
+        "};
 
-    fs::write("src/Sample2.php", tree.root_node().utf8_text(source_code.as_slice())?)?;
+        let file = format_lines(&cli, &mut session, Path::new("a.php"), original.as_bytes().to_vec(), &[2..3, 5..6]).unwrap();
 
-    Ok(())
+        assert_eq!(String::from_utf8(file.formatted).unwrap(), indoc! {"
+        <?php
+        function f( $x ){
+        return $x + 1;
+        }
+        $a=1;
+        $b = 2;
+        "});
+    }
 }
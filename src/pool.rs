@@ -0,0 +1,67 @@
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// The number of workers to use when none is given, one per available CPU.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1)
+}
+
+/// Runs `task` for every file on a pool of `jobs` worker threads.
+///
+/// `FixerRunner` holds its fixers as `Box<dyn Fixer>` and needs `&mut self`, so instead of sharing one, every
+/// worker creates its own state, e.g. a formatting session, through `create_worker` and keeps it for all the files
+/// it processes. Results are returned in the same order as `files`, whatever order the workers happened to finish in.
+pub fn run_parallel<S, T, C, F>(files: &[PathBuf], jobs: usize, create_worker: C, task: F) -> Vec<T>
+    where T: Send,
+          C: Fn() -> S + Sync,
+          F: Fn(&mut S, &Path) -> T + Sync
+{
+    let next = AtomicUsize::new(0);
+    let jobs = jobs.clamp(1, files.len().max(1));
+
+    let mut results: Vec<(usize, T)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| scope.spawn(|| {
+                let mut worker = create_worker();
+                let mut results = vec![];
+
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+
+                    match files.get(index) {
+                        Some(path) => results.push((index, task(&mut worker, path))),
+                        None => break,
+                    }
+                }
+
+                results
+            }))
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Formatter worker panicked."))
+            .collect()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::pool::run_parallel;
+
+    #[test]
+    fn it_keeps_the_order_of_the_input_files() {
+        let files: Vec<PathBuf> = (0..100).map(|index| PathBuf::from(format!("{}.php", index))).collect();
+
+        let results = run_parallel(&files, 8, || (), |_, path| path.to_owned());
+
+        assert_eq!(results, files);
+    }
+}
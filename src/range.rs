@@ -0,0 +1,363 @@
+use std::ops::{Range, RangeInclusive};
+
+use anyhow::bail;
+use similar::{Algorithm, capture_diff_slices, DiffOp};
+use tree_sitter::{Node, Tree};
+
+use crate::test_utilities::Edit;
+
+/// Converts 1-based, inclusive line numbers into the byte range they span, line breaks included.
+pub fn line_range_to_bytes(source_code: &[u8], lines: &RangeInclusive<usize>) -> Range<usize> {
+    let mut line_starts = vec![0];
+
+    line_starts.extend(source_code.iter().enumerate().filter(|(_, byte)| **byte == b'\n').map(|(offset, _)| offset + 1));
+
+    let start = line_starts.get(lines.start().saturating_sub(1)).copied().unwrap_or(source_code.len());
+    let end = line_starts.get(*lines.end()).copied().unwrap_or(source_code.len());
+
+    start..end.max(start)
+}
+
+/// The code to format for `range`: the smallest node spanning the whole of it, never a bare token since there is
+/// nothing to format in one. When `range` reaches into several children of that node, only those children are
+/// covered, so selecting two statements doesn't format the whole block around them.
+///
+/// Whitespace at either end of the range is ignored, otherwise selecting a whole line would always pull in the
+/// line break and with it the parent node.
+pub fn covering_range(tree: &Tree, source_code: &[u8], range: &Range<usize>) -> Range<usize> {
+    let mut start = range.start.min(source_code.len());
+    let mut end = range.end.min(source_code.len());
+
+    while start < end && source_code[start].is_ascii_whitespace() {
+        start += 1;
+    }
+
+    while end > start && source_code[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+
+    let root = tree.root_node();
+    let mut node = root.descendant_for_byte_range(start, end).unwrap_or(root);
+
+    while node.child_count() == 0 {
+        match node.parent() {
+            Some(parent) => node = parent,
+            None => break,
+        }
+    }
+
+    let children: Vec<Node> = node.children(&mut node.walk())
+        .filter(|child| intersects(&child.byte_range(), &(start..end)))
+        .collect();
+
+    match (children.first(), children.last()) {
+        (Some(first), Some(last)) if first.id() != last.id() => first.start_byte()..last.end_byte(),
+        _ => node.byte_range(),
+    }
+}
+
+pub fn intersects(left: &Range<usize>, right: &Range<usize>) -> bool {
+    left.start < right.end && right.start < left.end || left == right
+}
+
+/// Moves `range` so it keeps covering the same code once `edit` has been applied to the source.
+pub fn shift_range(range: &Range<usize>, edit: &Edit) -> Range<usize> {
+    let old_end = edit.position + edit.deleted_length;
+    let new_end = edit.position + edit.inserted_text.len();
+
+    if old_end <= range.start {
+        return range.start - old_end + new_end..range.end - old_end + new_end;
+    }
+
+    if edit.position >= range.end {
+        return range.clone();
+    }
+
+    let start = range.start.min(edit.position);
+
+    match range.end > old_end {
+        true => start..range.end - old_end + new_end,
+        false => start..new_end,
+    }
+}
+
+fn leaves<'a>(node: Node<'a>, leaves: &mut Vec<Node<'a>>) {
+    match node.child_count() {
+        0 => leaves.push(node),
+        _ => node.children(&mut node.walk()).for_each(|child| self::leaves(child, leaves)),
+    }
+}
+
+/// Takes the tokens inside of `range` from the formatted source, along with the whitespace between them, and puts
+/// them in place of the same tokens in the original, leaving every byte outside of them untouched.
+///
+/// Both sources are lined up token by token, so this only works when formatting did not add or remove tokens.
+pub fn splice(original: &[u8], original_tree: &Tree, formatted: &[u8], formatted_tree: &Tree, range: &Range<usize>) -> anyhow::Result<Vec<u8>> {
+    let mut original_leaves = vec![];
+    let mut formatted_leaves = vec![];
+
+    leaves(original_tree.root_node(), &mut original_leaves);
+    leaves(formatted_tree.root_node(), &mut formatted_leaves);
+
+    let lined_up = original_leaves.len() == formatted_leaves.len() && original_leaves.iter()
+        .zip(&formatted_leaves)
+        .all(|(left, right)| left.kind() == right.kind() && original[left.byte_range()] == formatted[right.byte_range()]);
+
+    if !lined_up {
+        bail!("The formatted code does not line up with the original, the range can't be formatted on its own.");
+    }
+
+    let inside: Vec<usize> = original_leaves.iter()
+        .enumerate()
+        .filter(|(_, leaf)| leaf.start_byte() >= range.start && leaf.end_byte() <= range.end)
+        .map(|(index, _)| index)
+        .collect();
+
+    let (Some(first), Some(last)) = (inside.first(), inside.last()) else {
+        return Ok(original.to_vec());
+    };
+
+    let original_range = original_leaves[*first].start_byte()..original_leaves[*last].end_byte();
+    let formatted_range = formatted_leaves[*first].start_byte()..formatted_leaves[*last].end_byte();
+
+    let mut result = original[..original_range.start].to_vec();
+
+    result.extend_from_slice(&formatted[formatted_range]);
+    result.extend_from_slice(&original[original_range.end..]);
+
+    Ok(result)
+}
+
+/// Sorts `ranges` and merges the ones that overlap or touch.
+pub fn merge(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| range.start);
+
+    ranges.into_iter().fold(vec![], |mut merged: Vec<Range<usize>>, range| {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+
+        merged
+    })
+}
+
+/// A part of the source code changed by edits, with the bytes it spans before and after them.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// The parts of the source code changed by `edits`, sorted and not overlapping, once they are all applied.
+pub fn changes(edits: &[Edit]) -> Vec<Change> {
+    let mut delta = 0;
+
+    edits.iter()
+        .map(|edit| {
+            let start = (edit.position as isize + delta) as usize;
+
+            delta += edit.inserted_text.len() as isize - edit.deleted_length as isize;
+
+            Change { old: edit.position..edit.position + edit.deleted_length, new: start..start + edit.inserted_text.len() }
+        })
+        .collect()
+}
+
+/// Where `position` of the original ends up, when it is not inside any of `changes` and follows the first `count`.
+fn map_position(changes: &[Change], count: usize, position: usize) -> usize {
+    match count.checked_sub(1) {
+        Some(last) => position - changes[last].old.end + changes[last].new.end,
+        None => position,
+    }
+}
+
+/// The leaves of `tree` lying within `range`, leaving out the empty ones.
+fn leaves_within<'a>(tree: &'a Tree, range: &Range<usize>) -> Vec<Node<'a>> {
+    let root = tree.root_node();
+    let node = root.descendant_for_byte_range(range.start, range.end)
+        .filter(|node| node.start_byte() <= range.start && node.end_byte() >= range.end)
+        .unwrap_or(root);
+
+    let mut leaves = vec![];
+
+    self::leaves(node, &mut leaves);
+    leaves.retain(|leaf| !leaf.byte_range().is_empty() && leaf.start_byte() >= range.start && leaf.end_byte() <= range.end);
+
+    leaves
+}
+
+/// A part of the original lined up with the same part of the formatted code, along with the regions within it.
+struct Window<'a> {
+    old: Range<usize>,
+    new: Range<usize>,
+    regions: Vec<&'a Range<usize>>,
+}
+
+/// The edits putting the protected `regions` of `original` back into `formatted`, byte for byte, once `changes` turned
+/// the one into the other.
+///
+/// Only the regions the changes reach into are looked at. Their tokens are lined up with the ones of the formatted
+/// code within the changes, so a region can be found again even when the fixers added or removed tokens around it.
+/// Everything between the first and the last token of a region is restored, the whitespace before it stays formatted
+/// so the region is still indented along with the surrounding code.
+pub fn restore(original: &[u8], original_tree: &Tree, formatted: &[u8], formatted_tree: &Tree, regions: &[Range<usize>], changes: &[Change]) -> anyhow::Result<Vec<Edit>> {
+    let mut windows: Vec<Window> = vec![];
+
+    for region in regions {
+        // Changes right in front of or right after the region, e.g. to the whitespace around a string, leave it as is.
+        let first = changes.partition_point(|change| change.old.end <= region.start);
+        let count = changes[first..].iter().take_while(|change| change.old.start < region.end).count();
+
+        if count == 0 {
+            continue;
+        }
+
+        let (first_change, last_change) = (&changes[first], &changes[first + count - 1]);
+        let old = region.start.min(first_change.old.start)..region.end.max(last_change.old.end);
+
+        let new = match first_change.old.start <= region.start {
+            true => first_change.new.start,
+            false => map_position(changes, first, region.start),
+        }..match last_change.old.end >= region.end {
+            true => last_change.new.end,
+            false => map_position(changes, first + count, region.end),
+        };
+
+        match windows.last_mut() {
+            Some(window) if old.start < window.old.end => {
+                window.old.end = window.old.end.max(old.end);
+                window.new.end = window.new.end.max(new.end);
+                window.regions.push(region);
+            }
+            _ => windows.push(Window { old, new, regions: vec![region] }),
+        }
+    }
+
+    let mut edits = vec![];
+
+    for window in windows {
+        let original_leaves = leaves_within(original_tree, &window.old);
+        let formatted_leaves = leaves_within(formatted_tree, &window.new);
+
+        let original_tokens: Vec<_> = original_leaves.iter().map(|leaf| (leaf.kind(), &original[leaf.byte_range()])).collect();
+        let formatted_tokens: Vec<_> = formatted_leaves.iter().map(|leaf| (leaf.kind(), &formatted[leaf.byte_range()])).collect();
+
+        let mut lined_up = vec![None; original_tokens.len()];
+
+        for operation in capture_diff_slices(Algorithm::Myers, &original_tokens, &formatted_tokens) {
+            if let DiffOp::Equal { old_index, new_index, len } = operation {
+                (0..len).for_each(|offset| lined_up[old_index + offset] = Some(new_index + offset));
+            }
+        }
+
+        for region in window.regions {
+            let first = original_leaves.partition_point(|leaf| leaf.start_byte() < region.start);
+            let last = original_leaves.partition_point(|leaf| leaf.end_byte() <= region.end);
+
+            if first >= last {
+                continue;
+            }
+
+            let (Some(formatted_first), Some(formatted_last)) = (lined_up[first], lined_up[last - 1]) else {
+                bail!("The protected code at line {} could not be preserved", original_leaves[first].start_position().row + 1);
+            };
+
+            let protected = original_leaves[first].start_byte()..original_leaves[last - 1].end_byte();
+            let replaced = formatted_leaves[formatted_first].start_byte()..formatted_leaves[formatted_last].end_byte();
+
+            if original[protected.clone()] != formatted[replaced.clone()] {
+                edits.push(Edit { position: replaced.start, deleted_length: replaced.len(), inserted_text: original[protected].to_vec() });
+            }
+        }
+    }
+
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::fixer::FixerRunner;
+    use crate::fixers::normalizer_fixer::NormalizerFixer;
+    use crate::range::{Change, changes, line_range_to_bytes, shift_range};
+    use crate::test_utilities::Edit;
+
+    #[test]
+    fn it_converts_lines_to_bytes() {
+        let source_code = b"<?php\n$a=1;\n$b=2;\n";
+
+        assert_eq!(line_range_to_bytes(source_code, &(2..=2)), 6..12);
+        assert_eq!(line_range_to_bytes(source_code, &(2..=9)), 6..18);
+    }
+
+    #[test]
+    fn it_shifts_ranges_after_edits() {
+        let edit = Edit { position: 2, deleted_length: 1, inserted_text: b"   ".to_vec() };
+
+        assert_eq!(shift_range(&(5..8), &edit), 7..10);
+        assert_eq!(shift_range(&(0..2), &edit), 0..2);
+        assert_eq!(shift_range(&(1..4), &edit), 1..6);
+    }
+
+    #[test]
+    fn it_tells_what_the_edits_changed() {
+        let edit = |position, deleted_length, inserted_text: &str| Edit { position, deleted_length, inserted_text: inserted_text.as_bytes().to_vec() };
+
+        // "abcdefghij" becomes "abXXdehiZj".
+        assert_eq!(changes(&[edit(2, 1, "XX"), edit(5, 2, ""), edit(9, 0, "Z")]), vec![
+            Change { old: 2..3, new: 2..4 },
+            Change { old: 5..7, new: 6..6 },
+            Change { old: 9..9, new: 8..9 },
+        ]);
+    }
+
+    #[test]
+    fn it_only_formats_the_given_range() {
+        let mut source_code = indoc! {"
+        <?php
+        $a=1;
+        $b=2;
+        $c=3;
+        "}.as_bytes().to_vec();
+
+        let mut runner = FixerRunner::new();
+        runner.add_fixer(Box::new(NormalizerFixer {}));
+
+        let range = line_range_to_bytes(&source_code, &(3..=3));
+        runner.execute_range(&mut source_code, range).unwrap();
+
+        assert_eq!(String::from_utf8(source_code).unwrap(), indoc! {"
+        <?php
+        $a=1;
+        $b = 2;
+        $c=3;
+        "});
+    }
+
+    #[test]
+    fn it_only_formats_the_statements_within_the_given_range() {
+        let mut source_code = indoc! {"
+        <?php
+        $a=1;
+        $b=2;
+        $c=3;
+        $d=4;
+        "}.as_bytes().to_vec();
+
+        let mut runner = FixerRunner::new();
+        runner.add_fixer(Box::new(NormalizerFixer {}));
+
+        let range = line_range_to_bytes(&source_code, &(3..=4));
+        runner.execute_range(&mut source_code, range).unwrap();
+
+        assert_eq!(String::from_utf8(source_code).unwrap(), indoc! {"
+        <?php
+        $a=1;
+        $b = 2;
+        $c = 3;
+        $d=4;
+        "});
+    }
+}
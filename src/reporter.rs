@@ -0,0 +1,22 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+/// The outcome of checking a single file, as handed to a `Reporter`.
+pub struct FileReport {
+    pub path: PathBuf,
+    pub changed: bool,
+    /// The 1-based line of the first change, when the file would be changed.
+    pub line: Option<usize>,
+    /// The fixers that would change the file, in the order they ran.
+    pub applied_fixers: Vec<String>,
+    /// Why the file could not be formatted, e.g. a parse error.
+    pub error: Option<String>,
+}
+
+/// Renders the results of a check run in a specific output format.
+///
+/// Reporters only turn `FileReport`s into text, adding a format means implementing this trait in
+/// `reporters` and registering it in `reporters::create_reporter`.
+pub trait Reporter {
+    fn report(&self, files: &[FileReport], output: &mut dyn Write) -> std::io::Result<()>;
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use crate::reporter::{FileReport, Reporter};
+use crate::reporters::escape_xml;
+
+pub struct CheckstyleReporter {}
+
+impl Reporter for CheckstyleReporter {
+    fn report(&self, files: &[FileReport], output: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(output, r#"<checkstyle version="4.3">"#)?;
+
+        for file in files {
+            writeln!(output, r#"  <file name="{}">"#, escape_xml(&file.path.to_string_lossy()))?;
+
+            if let Some(error) = &file.error {
+                writeln!(
+                    output,
+                    r#"    <error line="1" severity="error" message="{}" source="php-code-formatter.parse"/>"#,
+                    escape_xml(error),
+                )?;
+            }
+
+            if file.changed {
+                for fixer in &file.applied_fixers {
+                    writeln!(
+                        output,
+                        r#"    <error line="{}" severity="warning" message="File is not formatted according to {}." source="php-code-formatter.{}"/>"#,
+                        file.line.unwrap_or(1),
+                        escape_xml(fixer),
+                        escape_xml(fixer),
+                    )?;
+                }
+            }
+
+            writeln!(output, "  </file>")?;
+        }
+
+        writeln!(output, "</checkstyle>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+
+    use crate::reporter::{FileReport, Reporter};
+    use crate::reporters::checkstyle_reporter::CheckstyleReporter;
+
+    #[test]
+    fn it_reports_one_error_per_applied_fixer() {
+        let files = vec![
+            FileReport { path: PathBuf::from("a.php"), changed: true, line: Some(2), applied_fixers: vec!["normalizer".to_owned()], error: None },
+            FileReport { path: PathBuf::from("b.php"), changed: false, line: None, applied_fixers: vec![], error: Some("Syntax error in <b.php>".to_owned()) },
+            FileReport { path: PathBuf::from("c.php"), changed: false, line: None, applied_fixers: vec![], error: None },
+        ];
+
+        let mut output = vec![];
+        CheckstyleReporter {}.report(&files, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), indoc! {r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <checkstyle version="4.3">
+          <file name="a.php">
+            <error line="2" severity="warning" message="File is not formatted according to normalizer." source="php-code-formatter.normalizer"/>
+          </file>
+          <file name="b.php">
+            <error line="1" severity="error" message="Syntax error in &lt;b.php&gt;" source="php-code-formatter.parse"/>
+          </file>
+          <file name="c.php">
+          </file>
+        </checkstyle>
+        "#});
+    }
+}
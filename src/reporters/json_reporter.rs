@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::reporter::{FileReport, Reporter};
+
+pub struct JsonReporter {}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    files: Vec<File<'a>>,
+    summary: Summary,
+}
+
+#[derive(Serialize)]
+struct File<'a> {
+    path: Cow<'a, str>,
+    changed: bool,
+    line: Option<usize>,
+    fixers: &'a [String],
+    error: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    total: usize,
+    changed: usize,
+    failed: usize,
+}
+
+impl Reporter for JsonReporter {
+    fn report(&self, files: &[FileReport], output: &mut dyn Write) -> std::io::Result<()> {
+        let report = Report {
+            files: files
+                .iter()
+                .map(|file| File {
+                    path: file.path.to_string_lossy(),
+                    changed: file.changed,
+                    line: file.line,
+                    fixers: &file.applied_fixers,
+                    error: file.error.as_deref(),
+                })
+                .collect(),
+            summary: Summary {
+                total: files.len(),
+                changed: files.iter().filter(|file| file.changed).count(),
+                failed: files.iter().filter(|file| file.error.is_some()).count(),
+            },
+        };
+
+        serde_json::to_writer(&mut *output, &report)?;
+
+        writeln!(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::reporter::{FileReport, Reporter};
+    use crate::reporters::json_reporter::JsonReporter;
+
+    #[test]
+    fn it_reports_every_file_with_a_summary() {
+        let files = vec![
+            FileReport { path: PathBuf::from("a.php"), changed: true, line: Some(2), applied_fixers: vec!["normalizer".to_owned()], error: None },
+            FileReport { path: PathBuf::from("b \"quoted\".php"), changed: false, line: None, applied_fixers: vec![], error: Some("Syntax error".to_owned()) },
+        ];
+
+        let mut output = vec![];
+        JsonReporter {}.report(&files, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "{\"files\":[",
+                "{\"path\":\"a.php\",\"changed\":true,\"line\":2,\"fixers\":[\"normalizer\"],\"error\":null},",
+                "{\"path\":\"b \\\"quoted\\\".php\",\"changed\":false,\"line\":null,\"fixers\":[],\"error\":\"Syntax error\"}",
+                "],\"summary\":{\"total\":2,\"changed\":1,\"failed\":1}}\n",
+            )
+        );
+    }
+}
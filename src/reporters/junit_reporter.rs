@@ -0,0 +1,79 @@
+use std::io::Write;
+
+use crate::reporter::{FileReport, Reporter};
+use crate::reporters::escape_xml;
+
+pub struct JunitReporter {}
+
+impl Reporter for JunitReporter {
+    fn report(&self, files: &[FileReport], output: &mut dyn Write) -> std::io::Result<()> {
+        let failures = files.iter().filter(|file| file.changed).count();
+        let errors = files.iter().filter(|file| file.error.is_some()).count();
+        let counts = format!(r#"tests="{}" failures="{}" errors="{}""#, files.len(), failures, errors);
+
+        writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(output, r#"<testsuites name="php-code-formatter" {}>"#, counts)?;
+        writeln!(output, r#"  <testsuite name="php-code-formatter" {}>"#, counts)?;
+
+        for file in files {
+            let name = escape_xml(&file.path.to_string_lossy());
+
+            if let Some(error) = &file.error {
+                writeln!(output, r#"    <testcase name="{}" classname="php-code-formatter">"#, name)?;
+                writeln!(output, r#"      <error type="parse" message="{}"/>"#, escape_xml(error))?;
+                writeln!(output, "    </testcase>")?;
+            } else if file.changed {
+                writeln!(output, r#"    <testcase name="{}" classname="php-code-formatter">"#, name)?;
+                writeln!(
+                    output,
+                    r#"      <failure type="formatting" message="File is not formatted, first change on line {}.">Fixers: {}</failure>"#,
+                    file.line.unwrap_or(1),
+                    escape_xml(&file.applied_fixers.join(", ")),
+                )?;
+                writeln!(output, "    </testcase>")?;
+            } else {
+                writeln!(output, r#"    <testcase name="{}" classname="php-code-formatter"/>"#, name)?;
+            }
+        }
+
+        writeln!(output, "  </testsuite>")?;
+        writeln!(output, "</testsuites>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+
+    use crate::reporter::{FileReport, Reporter};
+    use crate::reporters::junit_reporter::JunitReporter;
+
+    #[test]
+    fn it_reports_a_testcase_per_file() {
+        let files = vec![
+            FileReport { path: PathBuf::from("a.php"), changed: true, line: Some(2), applied_fixers: vec!["normalizer".to_owned()], error: None },
+            FileReport { path: PathBuf::from("b.php"), changed: false, line: None, applied_fixers: vec![], error: Some("Syntax error".to_owned()) },
+            FileReport { path: PathBuf::from("c.php"), changed: false, line: None, applied_fixers: vec![], error: None },
+        ];
+
+        let mut output = vec![];
+        JunitReporter {}.report(&files, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), indoc! {r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <testsuites name="php-code-formatter" tests="3" failures="1" errors="1">
+          <testsuite name="php-code-formatter" tests="3" failures="1" errors="1">
+            <testcase name="a.php" classname="php-code-formatter">
+              <failure type="formatting" message="File is not formatted, first change on line 2.">Fixers: normalizer</failure>
+            </testcase>
+            <testcase name="b.php" classname="php-code-formatter">
+              <error type="parse" message="Syntax error"/>
+            </testcase>
+            <testcase name="c.php" classname="php-code-formatter"/>
+          </testsuite>
+        </testsuites>
+        "#});
+    }
+}
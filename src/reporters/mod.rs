@@ -0,0 +1,38 @@
+pub mod checkstyle_reporter;
+pub mod json_reporter;
+pub mod junit_reporter;
+pub mod text_reporter;
+
+use clap::ValueEnum;
+
+use crate::reporter::Reporter;
+use crate::reporters::checkstyle_reporter::CheckstyleReporter;
+use crate::reporters::json_reporter::JsonReporter;
+use crate::reporters::junit_reporter::JunitReporter;
+use crate::reporters::text_reporter::TextReporter;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Checkstyle,
+    Junit,
+}
+
+pub fn create_reporter(format: ReportFormat) -> Box<dyn Reporter> {
+    match format {
+        ReportFormat::Text => Box::new(TextReporter {}),
+        ReportFormat::Json => Box::new(JsonReporter {}),
+        ReportFormat::Checkstyle => Box::new(CheckstyleReporter {}),
+        ReportFormat::Junit => Box::new(JunitReporter {}),
+    }
+}
+
+pub fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
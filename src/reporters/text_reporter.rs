@@ -0,0 +1,43 @@
+use std::io::Write;
+
+use crate::reporter::{FileReport, Reporter};
+
+pub struct TextReporter {}
+
+impl Reporter for TextReporter {
+    fn report(&self, files: &[FileReport], output: &mut dyn Write) -> std::io::Result<()> {
+        let changed = files.iter().filter(|file| file.changed).count();
+
+        for file in files.iter().filter(|file| file.changed) {
+            writeln!(output, "Would reformat {}", file.path.display())?;
+        }
+
+        writeln!(output, "{} of {} files would be reformatted.", changed, files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+
+    use crate::reporter::{FileReport, Reporter};
+    use crate::reporters::text_reporter::TextReporter;
+
+    #[test]
+    fn it_lists_the_files_that_would_change() {
+        let files = vec![
+            FileReport { path: PathBuf::from("a.php"), changed: true, line: Some(2), applied_fixers: vec![], error: None },
+            FileReport { path: PathBuf::from("b.php"), changed: false, line: None, applied_fixers: vec![], error: None },
+        ];
+
+        let mut output = vec![];
+        TextReporter {}.report(&files, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), indoc! {"
+        Would reformat a.php
+        1 of 2 files would be reformatted.
+        "});
+    }
+}
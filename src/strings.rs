@@ -0,0 +1,237 @@
+use std::ops::Range;
+
+use tree_sitter::{Node, Tree};
+
+use crate::range::Change;
+use crate::test_utilities::Edit;
+
+/// Whether `node` is a literal whose content ends up in the program at runtime, none of it is a matter of layout.
+pub fn is_string_like(node: &Node) -> bool {
+    node.is_named() && matches!(node.kind(), "string" | "encapsed_string" | "heredoc" | "nowdoc" | "shell_command_expression")
+}
+
+/// Whether `node` is part of a string, e.g. an expression interpolated into it.
+pub fn is_inside_string(node: &Node) -> bool {
+    let mut parent = node.parent();
+
+    while let Some(node) = parent {
+        if is_string_like(&node) {
+            return true;
+        }
+
+        parent = node.parent();
+    }
+
+    false
+}
+
+fn strings<'a>(node: Node<'a>, strings: &mut Vec<Node<'a>>) {
+    match is_string_like(&node) {
+        true => strings.push(node),
+        false => node.children(&mut node.walk()).for_each(|child| self::strings(child, strings)),
+    }
+}
+
+/// The byte ranges of every string literal, in order.
+pub fn string_regions(tree: &Tree) -> Vec<Range<usize>> {
+    let mut nodes = vec![];
+
+    strings(tree.root_node(), &mut nodes);
+
+    nodes.iter().map(|node| node.byte_range()).collect()
+}
+
+fn indentation(source_code: &[u8], offset: usize) -> usize {
+    let line_start = source_code[..offset].iter().rposition(|byte| *byte == b'\n').map_or(0, |offset| offset + 1);
+
+    source_code[line_start..].iter().take_while(|byte| matches!(byte, b' ' | b'\t')).count()
+}
+
+/// Moves the body and the closing marker of `heredoc` by `delta` columns, which leaves its value untouched since
+/// the indentation of the closing marker is removed from every line. The closing marker is never moved further left
+/// than the first column.
+fn reindent(heredoc: &[u8], marker: usize, delta: isize) -> Vec<u8> {
+    let delta = delta.max(-(marker as isize));
+    let mut lines = heredoc.split(|byte| *byte == b'\n');
+    let mut result = lines.next().unwrap_or_default().to_vec();
+
+    for line in lines {
+        result.push(b'\n');
+
+        match delta {
+            _ if line.iter().all(u8::is_ascii_whitespace) => result.extend_from_slice(line),
+            delta if delta > 0 => {
+                result.extend(std::iter::repeat_n(b' ', delta as usize));
+                result.extend_from_slice(line);
+            }
+            delta => {
+                let removable = line.iter().take(delta.unsigned_abs()).take_while(|byte| matches!(byte, b' ' | b'\t')).count();
+
+                result.extend_from_slice(&line[removable..]);
+            }
+        }
+    }
+
+    result
+}
+
+fn heredocs<'a>(node: Node<'a>, heredocs: &mut Vec<Node<'a>>) {
+    match node.kind() {
+        "heredoc" | "nowdoc" => heredocs.push(node),
+        _ => node.children(&mut node.walk()).for_each(|child| self::heredocs(child, heredocs)),
+    }
+}
+
+/// Whether any of `changes` reaches into a heredoc or nowdoc among the `strings` of `source_code`, or into the line
+/// it starts on, which moves it along.
+pub fn moves_heredocs(source_code: &[u8], strings: &[Range<usize>], changes: &[Change]) -> bool {
+    strings.iter()
+        .filter(|string| source_code[string.start..].starts_with(b"<<<"))
+        .any(|heredoc| {
+            let line_start = source_code[..heredoc.start].iter().rposition(|byte| *byte == b'\n').map_or(0, |offset| offset + 1);
+
+            changes.iter().any(|change| change.old.start < heredoc.end && change.old.end >= line_start)
+        })
+}
+
+/// The edits re-indenting every flexible heredoc and nowdoc of `formatted` along with the line it starts on, its
+/// body is otherwise kept byte for byte.
+///
+/// A heredoc is flexible when its closing marker is indented. The others are left alone, moving their closing
+/// marker would be a syntax error before PHP 7.3.
+pub fn reindent_heredocs(original: &[u8], original_tree: &Tree, formatted: &[u8], formatted_tree: &Tree) -> Vec<Edit> {
+    let mut original_heredocs = vec![];
+    let mut formatted_heredocs = vec![];
+
+    heredocs(original_tree.root_node(), &mut original_heredocs);
+    heredocs(formatted_tree.root_node(), &mut formatted_heredocs);
+
+    let mut edits = vec![];
+
+    if original_heredocs.len() != formatted_heredocs.len() {
+        return edits;
+    }
+
+    for (original_heredoc, formatted_heredoc) in original_heredocs.iter().zip(&formatted_heredocs) {
+        let mut cursor = original_heredoc.walk();
+
+        let Some(end) = original_heredoc.children(&mut cursor).find(|child| child.kind() == "heredoc_end") else {
+            continue;
+        };
+
+        let marker = indentation(original, end.start_byte());
+        let delta = indentation(formatted, formatted_heredoc.start_byte()) as isize - indentation(original, original_heredoc.start_byte()) as isize;
+
+        if marker == 0 || delta == 0 || original[original_heredoc.byte_range()] != formatted[formatted_heredoc.byte_range()] {
+            continue;
+        }
+
+        edits.push(Edit {
+            position: formatted_heredoc.start_byte(),
+            deleted_length: formatted_heredoc.byte_range().len(),
+            inserted_text: reindent(&original[original_heredoc.byte_range()], marker, delta),
+        });
+    }
+
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::fixer::{default_runner, FixerRunner};
+    use crate::range::Change;
+    use crate::strings::{moves_heredocs, reindent_heredocs, string_regions};
+
+    #[test]
+    fn it_formats_code_around_flexible_heredocs() {
+        let mut source_code = indoc! {"
+            <?php
+            function f() {
+                $a = <<<EOT
+                    x
+                      y
+                    EOT;
+                return $a;
+            }
+        "}.as_bytes().to_vec();
+
+        default_runner().execute(&mut source_code).unwrap();
+
+        assert_eq!(String::from_utf8(source_code).unwrap(), indoc! {"
+            <?php
+            function f()
+            {
+            $a = <<<EOT
+                x
+                  y
+                EOT;
+            return $a;
+            }
+        "});
+    }
+
+    #[test]
+    fn it_tells_which_changes_move_heredocs() {
+        let source_code = b"<?php\n$a = 'x';\n$b = <<<EOT\n  y\n  EOT;\n";
+        let mut parser = FixerRunner::create_parser().unwrap();
+        let strings = string_regions(&FixerRunner::parse(&mut parser, source_code).unwrap());
+
+        assert!(!moves_heredocs(source_code, &strings, &[Change { old: 8..9, new: 8..8 }]));
+        assert!(moves_heredocs(source_code, &strings, &[Change { old: 16..16, new: 16..20 }]));
+        assert!(moves_heredocs(source_code, &strings, &[Change { old: 21..21, new: 21..22 }]));
+    }
+
+    #[test]
+    fn it_moves_flexible_heredocs_along_with_their_line() {
+        let original = indoc! {"
+            <?php
+            if (true) {
+            $a = <<<EOT
+              x
+                y
+              EOT;
+            $b = <<<EOT
+            z
+            EOT;
+            }
+        "};
+
+        let formatted = indoc! {"
+            <?php
+            if (true) {
+                $a = <<<EOT
+              x
+                y
+              EOT;
+                $b = <<<EOT
+            z
+            EOT;
+            }
+        "};
+
+        let mut parser = FixerRunner::create_parser().unwrap();
+        let original_tree = FixerRunner::parse(&mut parser, original.as_bytes()).unwrap();
+        let formatted_tree = FixerRunner::parse(&mut parser, formatted.as_bytes()).unwrap();
+
+        let mut result = formatted.as_bytes().to_vec();
+
+        for edit in reindent_heredocs(original.as_bytes(), &original_tree, formatted.as_bytes(), &formatted_tree).iter().rev() {
+            result.splice(edit.position..edit.position + edit.deleted_length, edit.inserted_text.iter().copied());
+        }
+
+        assert_eq!(String::from_utf8(result).unwrap(), indoc! {"
+            <?php
+            if (true) {
+                $a = <<<EOT
+                  x
+                    y
+                  EOT;
+                $b = <<<EOT
+            z
+            EOT;
+            }
+        "});
+    }
+}
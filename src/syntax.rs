@@ -0,0 +1,135 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Tree};
+
+use crate::line_index::LineIndex;
+
+/// How much of an unexpected piece of code is quoted in a diagnostic.
+const MAX_QUOTED: usize = 40;
+
+/// The first place where the parser could not make sense of the source code.
+#[derive(Debug)]
+pub struct SyntaxError {
+    /// 1-based line and character column.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// The whole line containing the error.
+    pub snippet: String,
+}
+
+impl SyntaxError {
+    fn new(node: Node, source_code: &[u8]) -> Self {
+        let index = LineIndex::new(source_code);
+        let start = node.start_byte();
+
+        let message = match node.is_missing() {
+            true => format!("missing `{}`", node.kind()),
+            false => {
+                let text = String::from_utf8_lossy(&source_code[node.byte_range()]);
+                let text = text.lines().next().unwrap_or_default().trim();
+
+                match text.chars().count() > MAX_QUOTED {
+                    true => format!("unexpected `{}…`", text.chars().take(MAX_QUOTED).collect::<String>()),
+                    false => format!("unexpected `{}`", text),
+                }
+            }
+        };
+
+        Self {
+            line: node.start_position().row + 1,
+            column: index.character_column(source_code, start) + 1,
+            message,
+            snippet: String::from_utf8_lossy(&source_code[index.line(source_code, start)]).into_owned(),
+        }
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        let indentation: String = self.snippet.chars().take(self.column - 1)
+            .map(|character| if character == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        write!(formatter, "syntax error at {}:{}, {}\n    {}\n    {}^", self.line, self.column, self.message, self.snippet, indentation)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+fn first_error(node: Node) -> Option<Node> {
+    if node.is_missing() {
+        return Some(node);
+    }
+
+    // The parser may wrap everything up to the end of the file in a single error, pointing at its start would only
+    // ever blame the opening tag. A missing token inside of it, or else the first token it could not place, is the
+    // actual culprit.
+    if node.is_error() {
+        let culprit = node.children(&mut node.walk()).find_map(first_error)
+            .or_else(|| node.children(&mut node.walk()).find(|child| child.child_count() == 0 && child.kind() != "php_tag"));
+
+        return Some(culprit.unwrap_or(node));
+    }
+
+    if !node.has_error() {
+        return None;
+    }
+
+    node.children(&mut node.walk()).find_map(first_error)
+}
+
+/// Fails with a diagnostic pointing at the first `ERROR` or `MISSING` node of `tree`.
+pub fn ensure_valid(tree: &Tree, source_code: &[u8]) -> Result<(), SyntaxError> {
+    match first_error(tree.root_node()) {
+        Some(node) => Err(SyntaxError::new(node, source_code)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixer::{default_runner, FixerRunner};
+    use crate::syntax::ensure_valid;
+
+    #[test]
+    fn it_points_at_the_first_syntax_error() {
+        let source_code = b"<?php\n$a = 1;\nif ($a {}\n".to_vec();
+        let mut parser = FixerRunner::create_parser().unwrap();
+        let tree = FixerRunner::parse(&mut parser, &source_code).unwrap();
+
+        let error = ensure_valid(&tree, &source_code).unwrap_err();
+
+        assert_eq!((error.line, error.column), (3, 7));
+        assert_eq!(error.to_string(), "syntax error at 3:7, missing `)`\n    if ($a {}\n          ^");
+    }
+
+    #[test]
+    fn it_points_inside_of_an_error_wrapping_the_whole_file() {
+        let mut parser = FixerRunner::create_parser().unwrap();
+
+        let source_code = b"<?php\n$a=1;\nif ($x {\n$b=2;\n".to_vec();
+        let tree = FixerRunner::parse(&mut parser, &source_code).unwrap();
+
+        assert!(tree.root_node().is_error());
+        assert_eq!(ensure_valid(&tree, &source_code).unwrap_err().to_string(), "syntax error at 3:7, missing `)`\n    if ($x {\n          ^");
+
+        let source_code = b"<?php\n$a=1;\n}\n$b=2;\n".to_vec();
+        let tree = FixerRunner::parse(&mut parser, &source_code).unwrap();
+
+        assert_eq!(ensure_valid(&tree, &source_code).unwrap_err().to_string(), "syntax error at 3:1, unexpected `}`\n    }\n    ^");
+    }
+
+    #[test]
+    fn it_only_formats_valid_statements_when_syntax_errors_are_allowed() {
+        let mut runner = default_runner();
+        let mut source_code = b"<?php\n$a  =  1 ;\nif ($a {}\n".to_vec();
+
+        assert!(runner.execute(&mut source_code.clone()).is_err());
+
+        runner.allow_syntax_errors(true);
+        runner.execute(&mut source_code).unwrap();
+
+        assert_eq!(String::from_utf8(source_code).unwrap(), "<?php\n$a = 1;\nif ($a {}\n");
+    }
+}
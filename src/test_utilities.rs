@@ -1,4 +1,4 @@
-use tree_sitter::{InputEdit, Node, Parser, Point, Tree};
+use tree_sitter::{Node, Parser, Query};
 
 use crate::fixer::{Fixer, tree_sitter_php};
 
@@ -19,52 +19,29 @@ pub fn is_multiline(node: &Node) -> bool {
     node.start_position().row != node.end_position().row
 }
 
+/// The edits replacing the whitespace between every two children of `node` with the `spacing` for them.
+pub fn space_children(node: &Node, spacing: impl Fn(&Node, &Node) -> Vec<u8>) -> Vec<Edit> {
+    let children: Vec<Node> = node.children(&mut node.walk()).collect();
+
+    children.windows(2)
+        .map(|pair| Edit {
+            position: pair[0].end_byte(),
+            deleted_length: pair[1].start_byte() - pair[0].end_byte(),
+            inserted_text: spacing(&pair[0], &pair[1]),
+        })
+        .collect()
+}
+
 pub fn run_fixer(mut source_code: Vec<u8>, mut fixer: impl Fixer) -> Vec<u8> {
     let mut parser = Parser::new();
     let language = unsafe { tree_sitter_php() };
 
     parser.set_language(language).unwrap();
 
-    let mut tree = parser.parse(&source_code, None).unwrap();
+    let tree = parser.parse(&source_code, None).unwrap();
+    let query = Query::new(language, fixer.query()).unwrap();
 
-    fixer.execute(tree, &mut parser, &mut source_code, &language);
+    fixer.execute(tree, &mut parser, &mut source_code, &query, None).unwrap();
 
     source_code
 }
-
-pub fn perform_edit(tree: &mut Tree, input: &mut Vec<u8>, edit: &Edit) -> InputEdit {
-    let start_byte = edit.position;
-    let old_end_byte = edit.position + edit.deleted_length;
-    let new_end_byte = edit.position + edit.inserted_text.len();
-    let start_position = position_for_offset(input, start_byte);
-    let old_end_position = position_for_offset(input, old_end_byte);
-
-    input.splice(start_byte..old_end_byte, edit.inserted_text.iter().cloned());
-    let new_end_position = position_for_offset(input, new_end_byte);
-
-    let edit = InputEdit {
-        start_byte,
-        old_end_byte,
-        new_end_byte,
-        start_position,
-        old_end_position,
-        new_end_position,
-    };
-
-    tree.edit(&edit);
-
-    edit
-}
-
-fn position_for_offset(input: &Vec<u8>, offset: usize) -> Point {
-    let mut result = Point { row: 0, column: 0 };
-    for c in &input[0..offset] {
-        if *c as char == '\n' {
-            result.row += 1;
-            result.column = 0;
-        } else {
-            result.column += 1;
-        }
-    }
-    result
-}
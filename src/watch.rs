@@ -0,0 +1,136 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use anyhow::Context;
+use notify::{Config, Event, EventKind, PollWatcher, RecursiveMode, Watcher};
+
+use crate::cache::content_hash;
+use crate::discovery::{Discovery, is_php_file};
+use crate::formatter::FormattingSession;
+
+/// How long the directory has to stay quiet before a burst of events is handled.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// How often the directory is scanned when native file watching is unavailable.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+type Events = notify::Result<Event>;
+
+fn create_watcher(directory: &Path, sender: Sender<Events>) -> anyhow::Result<Box<dyn Watcher>> {
+    let native = notify::recommended_watcher(sender.clone()).and_then(|mut watcher| {
+        watcher.watch(directory, RecursiveMode::Recursive)?;
+
+        Ok(watcher)
+    });
+
+    match native {
+        Ok(watcher) => Ok(Box::new(watcher)),
+        Err(error) => {
+            eprintln!("Warning: native file watching is unavailable ({}), falling back to polling.", error);
+
+            let mut watcher = PollWatcher::new(sender, Config::default().with_poll_interval(POLL_INTERVAL))?;
+
+            watcher.watch(directory, RecursiveMode::Recursive)?;
+
+            Ok(Box::new(watcher))
+        }
+    }
+}
+
+/// Adds the PHP files created or modified by `event` to `pending`.
+fn collect(event: Events, discovery: &Discovery, pending: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            pending.extend(event.paths.into_iter().filter(|path| is_php_file(path) && !discovery.is_excluded(path)));
+        }
+        Ok(_) => {}
+        Err(error) => eprintln!("Warning: {}", error),
+    }
+}
+
+/// Blocks until something happens, then keeps collecting until no event arrived for `DEBOUNCE`, so that an editor
+/// saving through a temporary file, or a `git checkout`, is handled as one batch.
+fn next_batch(receiver: &Receiver<Events>, discovery: &Discovery) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut pending = BTreeSet::new();
+
+    collect(receiver.recv().context("The file watcher stopped")?, discovery, &mut pending);
+
+    while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+        collect(event, discovery, &mut pending);
+    }
+
+    Ok(pending)
+}
+
+/// Watches `directory` and reformats every PHP file saved in it, until the process is interrupted.
+pub fn watch(directory: &Path, discovery: &Discovery, session: &mut FormattingSession) -> anyhow::Result<()> {
+    let (sender, receiver) = channel();
+    let _watcher = create_watcher(directory, sender)?;
+
+    // The hash of every file this process wrote, so the events caused by our own writes are recognised and ignored.
+    let mut written: HashMap<PathBuf, u64> = HashMap::new();
+
+    println!("Watching {} for changes.", directory.display());
+
+    loop {
+        for path in next_batch(&receiver, discovery)? {
+            let Ok(original) = fs::read(&path) else {
+                continue;
+            };
+
+            if written.get(&path) == Some(&content_hash(&original)) {
+                continue;
+            }
+
+            let result = session.format(&path, original, None).and_then(|file| {
+                if file.is_changed() {
+                    file.write()?;
+                    written.insert(path.to_owned(), content_hash(&file.formatted));
+                }
+
+                Ok(file.is_changed())
+            });
+
+            match result {
+                Ok(true) => println!("Formatted {}", path.display()),
+                Ok(false) => {}
+                Err(error) => eprintln!("Failed to format {}: {:#}", path.display(), error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+    use notify::{Event, EventKind};
+
+    use crate::discovery::Discovery;
+    use crate::watch::collect;
+
+    #[test]
+    fn it_only_collects_saved_php_files() {
+        let discovery = Discovery::new(&["vendor/".to_owned()], false).unwrap();
+        let mut pending = BTreeSet::new();
+
+        let events = vec![
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("app/User.php")),
+            Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("app/Post.php")),
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("app/readme.md")),
+            Event::new(EventKind::Remove(RemoveKind::File)).add_path(PathBuf::from("app/Comment.php")),
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("vendor/Library.php")),
+        ];
+
+        for event in events {
+            collect(Ok(event), &discovery, &mut pending);
+        }
+
+        assert_eq!(pending.into_iter().collect::<Vec<_>>(), vec![PathBuf::from("app/Post.php"), PathBuf::from("app/User.php")]);
+    }
+}